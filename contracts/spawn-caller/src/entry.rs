@@ -0,0 +1,35 @@
+use core::result::Result;
+
+use ckb_std::ckb_types::core::ScriptHashType;
+use ckb_std::high_level;
+use common::{call, debug};
+
+use super::error::ScriptError;
+
+pub fn main() -> Result<(), ScriptError> {
+    debug!("====== Running spawn-caller ======");
+
+    // The args contains the code_hash and hash_type of the callee, spawn it with the args left.
+    let script = high_level::load_script()?;
+    let args = script.args().raw_data();
+    if args.len() < 33 {
+        return Err(ScriptError::InvalidArgs);
+    }
+
+    let mut code_hash = [0u8; 32];
+    code_hash.copy_from_slice(&args[..32]);
+    let hash_type = match args[32] {
+        0 => ScriptHashType::Data,
+        1 => ScriptHashType::Type,
+        2 => ScriptHashType::Data1,
+        4 => ScriptHashType::Data2,
+        _ => return Err(ScriptError::InvalidArgs),
+    };
+
+    debug!("Spawn the callee with hash_type {} ...", args[32]);
+    let arg = call::encode_arg(&args[33..]);
+    let ret = call::spawn_by_code_hash(&code_hash, hash_type, &[arg.as_c_str()], 8)?;
+    debug!("The callee returned {} bytes.", ret.len());
+
+    Ok(())
+}
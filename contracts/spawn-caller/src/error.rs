@@ -0,0 +1,18 @@
+common::script_error! {
+    /// Error
+    pub enum ScriptError {
+        IndexOutOfBound = 1,
+        ItemMissing = 2,
+        LengthNotEnough = 3,
+        Encoding = 4,
+        InvalidArgs = 5,
+        CalleeNotFound = 6,
+        CalleeFailed = 7,
+        CalleeEncoding = 8,
+        ExceededMaxContentLength = 9,
+        WrongMemoryLimit = 10,
+        ExceededMaxPeakMemory = 11,
+    }
+}
+
+common::impl_from_call_error!(ScriptError);
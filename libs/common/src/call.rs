@@ -0,0 +1,103 @@
+//! Wrappers of the exec and spawn syscalls, so a contract can call another contract in cell_deps by its code hash.
+//!
+//! Arguments are passed as hex encoded argv, because argv can not contain the `\0` byte. The callee of spawn can
+//! return data to the caller with `set_return_data`, the callee of exec never returns.
+
+use alloc::ffi::CString;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::ffi::CStr;
+
+use ckb_std::ckb_types::core::ScriptHashType;
+use ckb_std::error::SysError;
+use ckb_std::high_level;
+
+/// The maximum length of data returned by the callee of spawn.
+#[cfg(feature = "ckb2023")]
+pub const MAX_RETURN_DATA_LENGTH: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallError {
+    /// The callee is not found in cell_deps by the code hash and hash type.
+    CalleeNotFound,
+    /// The callee exited with a non-zero code, the code is kept for reporting.
+    CalleeFailed(i8),
+    /// The argument is not valid hex.
+    InvalidArgs,
+    /// The data loaded by the syscalls is not encoded correctly, like a corrupted return data.
+    Encoding,
+    /// The data returned by the callee of spawn is longer than MAX_RETURN_DATA_LENGTH.
+    ExceededMaxContentLength,
+    /// The memory_limit of spawn is not between 1 and 8.
+    WrongMemoryLimit,
+    /// The callee of spawn can not get enough memory, it is usually caused by spawning too deep.
+    ExceededMaxPeakMemory,
+    /// Any other error returned by the syscalls.
+    Syscall(SysError),
+}
+
+impl From<SysError> for CallError {
+    fn from(err: SysError) -> Self {
+        match err {
+            // The syscalls only return IndexOutOfBound when no cell dep matches the code hash.
+            SysError::IndexOutOfBound => CallError::CalleeNotFound,
+            SysError::Encoding => CallError::Encoding,
+            #[cfg(feature = "ckb2023")]
+            SysError::SpawnExceededMaxContentLength => CallError::ExceededMaxContentLength,
+            #[cfg(feature = "ckb2023")]
+            SysError::SpawnWrongMemoryLimit => CallError::WrongMemoryLimit,
+            #[cfg(feature = "ckb2023")]
+            SysError::SpawnExceededMaxPeakMemory => CallError::ExceededMaxPeakMemory,
+            err => CallError::Syscall(err),
+        }
+    }
+}
+
+/// Encode bytes as an argument of exec or spawn.
+pub fn encode_arg(data: &[u8]) -> CString {
+    high_level::encode_hex(data)
+}
+
+/// Decode the argument encoded by `encode_arg`, it is used in the callee.
+pub fn decode_arg(arg: &CStr) -> Result<Vec<u8>, CallError> {
+    high_level::decode_hex(arg).map_err(|_| CallError::InvalidArgs)
+}
+
+/// Replace the current script with the callee, this function only returns when the callee can not be executed.
+pub fn exec_by_code_hash(
+    code_hash: &[u8; 32],
+    hash_type: ScriptHashType,
+    argv: &[&CStr],
+) -> Result<Infallible, CallError> {
+    high_level::exec_cell(code_hash, hash_type, argv).map_err(CallError::from)
+}
+
+/// Run the callee in a child VM and wait for it, return the data set by the callee if it exited with 0.
+///
+/// The memory_limit is between 1 and 8, each one represents 0.5M memory.
+#[cfg(feature = "ckb2023")]
+pub fn spawn_by_code_hash(
+    code_hash: &[u8; 32],
+    hash_type: ScriptHashType,
+    argv: &[&CStr],
+    memory_limit: u64,
+) -> Result<Vec<u8>, CallError> {
+    let mut content = alloc::vec![0u8; MAX_RETURN_DATA_LENGTH];
+    let exit_code = high_level::spawn_cell(code_hash, hash_type, argv, memory_limit, &mut content)?;
+    if exit_code != 0 {
+        return Err(CallError::CalleeFailed(exit_code));
+    }
+
+    Ok(content)
+}
+
+/// Return data to the caller of spawn, it can only be called once in the callee.
+#[cfg(feature = "ckb2023")]
+pub fn set_return_data(data: &[u8]) -> Result<(), CallError> {
+    let len = ckb_std::syscalls::set_content(data)?;
+    if len as usize != data.len() {
+        return Err(CallError::Syscall(SysError::LengthNotEnough(data.len())));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! The envelope of versioned entities in cell data, so contracts can migrate the layout of their data.
+//!
+//! The cell data is laid out as below, all integers are in little-endian:
+//!
+//! ```text
+//! | version: u32 | entity_hash: [u8; 32] | payload: molecule encoded entity |
+//! ```
+//!
+//! The entity hash is the blake2b hash of the payload, it is checked when the envelope is parsed.
+
+#[cfg(feature = "no_std")]
+use alloc::vec::Vec;
+
+use molecule::prelude::Entity;
+
+use crate::util::blake2b_256;
+
+pub const VERSION_LENGTH: usize = 4;
+pub const HASH_LENGTH: usize = 32;
+pub const HEADER_LENGTH: usize = VERSION_LENGTH + HASH_LENGTH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityError {
+    /// The data is shorter than the header of the envelope.
+    LengthNotEnough,
+    /// The entity hash does not match the payload.
+    HashMismatch,
+    /// The payload can not be decoded as the expected entity.
+    Encoding,
+    /// Any other error returned by the syscalls.
+    #[cfg(feature = "no_std")]
+    Syscall(ckb_std::error::SysError),
+}
+
+#[cfg(feature = "no_std")]
+impl From<ckb_std::error::SysError> for EntityError {
+    fn from(err: ckb_std::error::SysError) -> Self {
+        EntityError::Syscall(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionedEntity<'a> {
+    pub version: u32,
+    pub hash: [u8; 32],
+    pub payload: &'a [u8],
+}
+
+impl<'a> VersionedEntity<'a> {
+    /// Parse the envelope and verify the entity hash.
+    pub fn from_slice(data: &'a [u8]) -> Result<Self, EntityError> {
+        if data.len() < HEADER_LENGTH {
+            return Err(EntityError::LengthNotEnough);
+        }
+
+        let mut version = [0u8; VERSION_LENGTH];
+        version.copy_from_slice(&data[..VERSION_LENGTH]);
+        let mut hash = [0u8; HASH_LENGTH];
+        hash.copy_from_slice(&data[VERSION_LENGTH..HEADER_LENGTH]);
+        let payload = &data[HEADER_LENGTH..];
+
+        if blake2b_256(payload) != hash {
+            return Err(EntityError::HashMismatch);
+        }
+
+        Ok(VersionedEntity {
+            version: u32::from_le_bytes(version),
+            hash,
+            payload,
+        })
+    }
+
+    /// Decode the payload as the entity, the caller should choose the entity type by the version.
+    pub fn to_entity<T: Entity>(&self) -> Result<T, EntityError> {
+        T::from_slice(self.payload).map_err(|_| EntityError::Encoding)
+    }
+}
+
+/// Wrap the payload with the envelope, it is the inverse of `VersionedEntity::from_slice`.
+pub fn encode(version: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    data.extend_from_slice(&version.to_le_bytes());
+    data.extend_from_slice(&blake2b_256(payload));
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Load the cell data and parse the envelope, the version and the payload are returned.
+#[cfg(feature = "no_std")]
+pub fn load_cell_entity(index: usize, source: ckb_std::ckb_constants::Source) -> Result<(u32, Vec<u8>), EntityError> {
+    let data = ckb_std::high_level::load_cell_data(index, source)?;
+    let entity = VersionedEntity::from_slice(&data)?;
+
+    Ok((entity.version, entity.payload.to_vec()))
+}
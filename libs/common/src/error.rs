@@ -0,0 +1,118 @@
+//! Macros to define the error enums of contracts.
+//!
+//! Each contract defines its error enum in its own `error.rs` with `script_error!`. The tests crate includes these
+//! files to resolve exit codes to variant names, so the file should only contain the macro invocations below.
+
+/// Define the error enum of a contract, the codes must be given explicitly.
+///
+/// ```ignore
+/// common::script_error! {
+///     /// Error
+///     pub enum ScriptError {
+///         IndexOutOfBound = 1,
+///         ItemMissing = 2,
+///         LengthNotEnough = 3,
+///         Encoding = 4,
+///     }
+/// }
+/// ```
+///
+/// The enum must have the variants IndexOutOfBound, ItemMissing, LengthNotEnough and Encoding, because it can be
+/// converted from SysError when the `no_std` feature is enabled.
+#[macro_export]
+macro_rules! script_error {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($variant:ident = $code:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(i8)]
+        pub enum $name {
+            $($variant = $code,)*
+        }
+
+        impl $name {
+            /// All variants with their codes.
+            pub const VARIANTS: &'static [(&'static str, i8)] = &[$((stringify!($variant), $code),)*];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($variant),)*
+                }
+            }
+        }
+
+        impl From<$name> for i8 {
+            fn from(err: $name) -> i8 {
+                err as i8
+            }
+        }
+
+        $crate::impl_from_sys_error!($name);
+    };
+}
+
+#[cfg(feature = "no_std")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_sys_error {
+    ($name:ty) => {
+        impl From<ckb_std::error::SysError> for $name {
+            fn from(err: ckb_std::error::SysError) -> Self {
+                use ckb_std::error::SysError::*;
+                match err {
+                    IndexOutOfBound => Self::IndexOutOfBound,
+                    ItemMissing => Self::ItemMissing,
+                    LengthNotEnough(_) => Self::LengthNotEnough,
+                    Encoding => Self::Encoding,
+                    // The spawn errors are converted by CallError, they are unexpected anywhere else.
+                    err => panic!("unexpected sys error {:?}", err),
+                }
+            }
+        }
+    };
+}
+
+// The tests crate only needs the error codes.
+#[cfg(not(feature = "no_std"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_sys_error {
+    ($name:ty) => {};
+}
+
+/// Convert CallError to the error enum of a contract which calls other contracts by `common::call`.
+///
+/// The enum must have the variants CalleeNotFound, CalleeFailed, InvalidArgs, CalleeEncoding,
+/// ExceededMaxContentLength, WrongMemoryLimit and ExceededMaxPeakMemory.
+#[cfg(feature = "no_std")]
+#[macro_export]
+macro_rules! impl_from_call_error {
+    ($name:ty) => {
+        impl From<$crate::call::CallError> for $name {
+            fn from(err: $crate::call::CallError) -> Self {
+                use $crate::call::CallError::*;
+                match err {
+                    CalleeNotFound => Self::CalleeNotFound,
+                    CalleeFailed(_) => Self::CalleeFailed,
+                    InvalidArgs => Self::InvalidArgs,
+                    Encoding => Self::CalleeEncoding,
+                    ExceededMaxContentLength => Self::ExceededMaxContentLength,
+                    WrongMemoryLimit => Self::WrongMemoryLimit,
+                    ExceededMaxPeakMemory => Self::ExceededMaxPeakMemory,
+                    Syscall(err) => err.into(),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "no_std"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! impl_from_call_error {
+    ($name:ty) => {};
+}
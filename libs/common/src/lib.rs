@@ -5,4 +5,8 @@ extern crate alloc;
 #[macro_use]
 pub mod macros;
 
+#[cfg(feature = "no_std")]
+pub mod call;
+pub mod entity;
+pub mod error;
 pub mod util;
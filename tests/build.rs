@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+// Collect the error enums of all contracts, so exit codes can be resolved to variant names without a hand-written
+// table. Each contracts/<name>/src/error.rs is included as a module named after the contract.
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let contracts_dir = manifest_dir.join("../contracts");
+    println!("cargo:rerun-if-changed={}", contracts_dir.display());
+
+    let mut names = fs::read_dir(&contracts_dir)
+        .expect("The contracts directory should be readable.")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("src/error.rs").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut modules = String::new();
+    let mut items = String::new();
+    for name in names {
+        let path = contracts_dir.join(&name).join("src/error.rs");
+        let enum_name = match find_enum_name(&path) {
+            Some(val) => val,
+            None => {
+                println!("cargo:warning=No error enum is found in {}", path.display());
+                continue;
+            }
+        };
+        let module = name.replace('-', "_");
+
+        modules += &format!(
+            "pub mod {module} {{\n    include!({path:?});\n}}\n\n\
+             impl From<{module}::{enum_name}> for ErrorCode {{\n    \
+                 fn from(err: {module}::{enum_name}) -> Self {{\n        \
+                     ErrorCode {{ contract: Some({name:?}), name: Some(err.name()), code: err as i8 }}\n    \
+                 }}\n\
+             }}\n\n",
+            path = path.canonicalize().unwrap().display().to_string(),
+        );
+        items += &format!("    ({name:?}, {module}::{enum_name}::VARIANTS),\n");
+    }
+
+    let content = format!(
+        "{modules}/// All contracts with their error variants and codes.\n\
+         pub const SCRIPT_ERRORS: &[(&str, &[(&str, i8)])] = &[\n{items}];\n"
+    );
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("script_errors.rs");
+    fs::write(out_path, content).expect("The script_errors.rs should be writable.");
+}
+
+// The enum is defined like `pub enum ScriptError {` in the script_error! macro.
+fn find_enum_name(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let rest = &content[content.find("pub enum ")? + "pub enum ".len()..];
+    let name = rest
+        .chars()
+        .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+        .collect::<String>();
+
+    (!name.is_empty()).then_some(name)
+}
@@ -4,3 +4,5 @@
 mod util;
 
 mod playground;
+mod spawn_caller;
+mod system_scripts;
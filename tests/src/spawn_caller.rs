@@ -0,0 +1,85 @@
+use serde_json::{json, Value};
+
+use crate::util::cell_generator::{register_cell_generator, CellGenerator};
+use crate::util::constants::*;
+use crate::util::template_generator::*;
+use crate::util::template_parser::*;
+
+/// The cells of spawn-caller are described by the callee, the args of the type script are generated from it.
+///
+/// ```json
+/// json!({
+///     "capacity": ...,
+///     "lock": Script,
+///     "type": { "code_hash": "{{spawn-caller}}" },
+///     "callee": "...", // the name of the callee binary, it is referenced by data hash with hash_type data2
+///     "callee_args": null | "0x..." // the args passed to the callee
+/// })
+/// ```
+struct SpawnCallerCellGenerator;
+
+impl CellGenerator for SpawnCallerCellGenerator {
+    fn gen_cell(&self, _version: u32, cell: Value) -> Value {
+        let callee = cell["callee"].as_str().expect("cell.callee is missing");
+        let callee_args = cell["callee_args"].as_str().unwrap_or("0x");
+        let callee_args = callee_args.strip_prefix("0x").unwrap_or(callee_args);
+
+        let mut type_ = cell["type"].clone();
+        // The hash_type 4 is data2 in the args of spawn-caller.
+        type_["args"] = json!(format!("0x{{{{data_hash:{}}}}}04{}", callee, callee_args));
+
+        json!({
+            "capacity": cell["capacity"],
+            "lock": cell["lock"],
+            "type": type_,
+            "header": cell["header"],
+            "label": cell["label"]
+        })
+    }
+}
+
+fn init(action: &str) -> TemplateGenerator {
+    register_cell_generator("spawn-caller", SpawnCallerCellGenerator);
+    let mut template = TemplateGenerator::new(action, None);
+
+    template.push_contract_cell("always-success", ContractType::Contract);
+    template.push_contract_cell("spawn-caller", ContractType::Contract);
+    // The always-success is already loaded as the lock, so it is not pushed again.
+    template.push_callee("always-success", ContractType::Contract, CallType::Spawn);
+
+    template.push_header_deps(json!({
+        "height": HEIGHT,
+        "timestamp": TIMESTAMP,
+    }));
+
+    template
+}
+
+#[test]
+fn test_spawn_callee() {
+    let mut template = init("spawn");
+    assert_eq!(template.as_json()["cell_deps"].as_array().unwrap().len(), 2);
+
+    template.push_input(
+        json!({
+            "capacity": 0,
+            "lock": {
+                "code_hash": "{{always-success}}"
+            },
+            "type": {
+                "code_hash": "{{spawn-caller}}"
+            },
+            "callee": "always-success",
+            "callee_args": "0xff"
+        }),
+        None,
+        None,
+    );
+    assert_eq!(
+        template.as_json()["inputs"][0]["previous_output"]["type"]["args"],
+        json!("0x{{data_hash:always-success}}04ff")
+    );
+    template.push_empty_witness();
+
+    test_tx(template.as_json());
+}
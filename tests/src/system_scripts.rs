@@ -0,0 +1,204 @@
+use serde_json::{json, Value};
+
+use crate::util;
+use crate::util::constants::*;
+use crate::util::signer::MultisigConfig;
+use crate::util::template_generator::*;
+use crate::util::template_parser::*;
+
+// The error code of secp256k1_blake160_sighash_all when the public key recovered does not match the args.
+const ERROR_PUBKEY_BLAKE160_HASH: i8 = -31;
+// The error codes of secp256k1_blake160_multisig_all.
+const ERROR_WITNESS_SIZE: i8 = -22;
+const ERROR_INCORRECT_SINCE_VALUE: i8 = -24;
+const ERROR_MULTISIG_VERIFICATION: i8 = -52;
+
+fn init(action: &str) -> TemplateGenerator {
+    let mut template = TemplateGenerator::new(action, None);
+
+    template.push_dep_group(vec![
+        ("secp256k1_blake160_sighash_all", ContractType::DeployedContract),
+        ("secp256k1_data", ContractType::DeployedSharedLib),
+    ]);
+
+    template
+}
+
+fn push_input_of(template: &mut TemplateGenerator, key_name: &str) -> usize {
+    let index = template.push_input(
+        json!({
+            "capacity": "1000 CKB",
+            "lock": gen_sighash_all_lock(key_name)
+        }),
+        None,
+        None,
+    );
+    template.push_empty_witness();
+    index
+}
+
+#[test]
+fn test_sighash_all() {
+    let mut template = init("transfer");
+    push_input_of(&mut template, "alice");
+    push_input_of(&mut template, "alice");
+    push_input_of(&mut template, "bob");
+    template.push_output(
+        json!({
+            "capacity": "2999 CKB",
+            "lock": gen_sighash_all_lock("carol")
+        }),
+        None,
+    );
+
+    // The inputs of alice are in the same lock group, so they only need one signature.
+    template.sign_input(0, "alice");
+    template.sign_input(2, "bob");
+
+    test_tx(template.as_json());
+}
+
+#[test]
+fn challenge_sighash_all_wrong_key() {
+    let mut template = init("transfer");
+    push_input_of(&mut template, "alice");
+    template.push_output(
+        json!({
+            "capacity": "999 CKB",
+            "lock": gen_sighash_all_lock("bob")
+        }),
+        None,
+    );
+
+    template.sign_input(0, "bob");
+
+    challenge_tx(template.as_json(), ERROR_PUBKEY_BLAKE160_HASH);
+}
+
+#[test]
+fn challenge_sighash_all_modified_tx() {
+    let mut template = init("transfer");
+    push_input_of(&mut template, "alice");
+    template.push_output(
+        json!({
+            "capacity": "999 CKB",
+            "lock": gen_sighash_all_lock("bob")
+        }),
+        None,
+    );
+    template.sign_input(0, "alice");
+
+    // Take the signature of the original transaction, then modify the transaction so the signature does not match.
+    let signed_tx = TemplateParser::from_data(template.as_json(), 350_000_000)
+        .build_tx()
+        .unwrap();
+    let mut tx = template.as_json();
+    tx["inputs"][0]["tmp_signer"] = Value::Null;
+    tx["witnesses"][0] = json!(util::bytes_to_hex(&signed_tx.witnesses().get(0).unwrap().raw_data()));
+    tx["outputs"][0]["capacity"] = json!(998 * ONE_CKB);
+
+    challenge_tx(tx, ERROR_PUBKEY_BLAKE160_HASH);
+}
+
+fn init_multisig(action: &str) -> TemplateGenerator {
+    let mut template = TemplateGenerator::new(action, None);
+
+    template.push_dep_group(vec![
+        ("secp256k1_blake160_multisig_all", ContractType::DeployedContract),
+        ("secp256k1_data", ContractType::DeployedSharedLib),
+    ]);
+
+    template
+}
+
+fn push_multisig_input(template: &mut TemplateGenerator, config: &MultisigConfig, since_opt: Option<u64>) -> usize {
+    let index = template.push_input(
+        json!({
+            "capacity": "1000 CKB",
+            "lock": gen_multisig_lock(config)
+        }),
+        since_opt,
+        None,
+    );
+    template.push_empty_witness();
+    template.push_output(
+        json!({
+            "capacity": "999 CKB",
+            "lock": gen_multisig_lock(config)
+        }),
+        None,
+    );
+    index
+}
+
+#[test]
+fn test_multisig_all() {
+    let config = MultisigConfig::new(&["alice", "bob", "carol"], 2);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, None);
+    // The signatures are sorted in the order of keys, so the order of key names does not matter.
+    template.sign_input_multisig(0, &config, &["carol", "alice"]);
+
+    test_tx(template.as_json());
+}
+
+#[test]
+fn test_multisig_all_require_first_n() {
+    let config = MultisigConfig::new(&["alice", "bob", "carol"], 2).require_first_n(1);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, None);
+    template.sign_input_multisig(0, &config, &["alice", "carol"]);
+
+    test_tx(template.as_json());
+}
+
+#[test]
+fn challenge_multisig_all_require_first_n() {
+    let config = MultisigConfig::new(&["alice", "bob", "carol"], 2).require_first_n(1);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, None);
+    // The first key is required.
+    template.sign_input_multisig(0, &config, &["bob", "carol"]);
+
+    challenge_tx(template.as_json(), ERROR_MULTISIG_VERIFICATION);
+}
+
+#[test]
+fn challenge_multisig_all_unknown_key() {
+    let config = MultisigConfig::new(&["alice", "bob", "carol"], 2);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, None);
+    template.sign_input_multisig(0, &config, &["alice", "dave"]);
+
+    challenge_tx(template.as_json(), ERROR_MULTISIG_VERIFICATION);
+}
+
+#[test]
+fn challenge_multisig_all_threshold() {
+    let config = MultisigConfig::new(&["alice", "bob", "carol"], 2);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, None);
+    template.sign_input_multisig(0, &config, &["alice"]);
+
+    challenge_tx(template.as_json(), ERROR_WITNESS_SIZE);
+}
+
+#[test]
+fn test_multisig_all_since() {
+    let config = MultisigConfig::new(&["alice", "bob"], 1).since(100);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, Some(100));
+    template.sign_input_multisig(0, &config, &["bob"]);
+
+    test_tx(template.as_json());
+}
+
+#[test]
+fn challenge_multisig_all_since() {
+    let config = MultisigConfig::new(&["alice", "bob"], 1).since(100);
+    let mut template = init_multisig("transfer");
+    push_multisig_input(&mut template, &config, Some(99));
+    template.sign_input_multisig(0, &config, &["bob"]);
+
+    challenge_tx(template.as_json(), ERROR_INCORRECT_SINCE_VALUE);
+}
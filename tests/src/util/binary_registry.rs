@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::{env, fs, io};
+
+use ckb_types::bytes;
+use ckb_types::packed::{Byte32, CellOutput};
+use lazy_static::lazy_static;
+use thiserror::Error;
+
+const BINARY_VERSION: &str = "BINARY_VERSION";
+const BINARY_PATH: &str = "BINARY_PATH";
+
+lazy_static! {
+    // All tests run in the same process, so every binary is only read from disk once.
+    static ref BINARY_CACHE: Mutex<HashMap<PathBuf, Arc<Binary>>> = Mutex::new(HashMap::new());
+}
+
+pub enum BinaryVersion {
+    Debug,
+    Release,
+}
+
+impl FromStr for BinaryVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "debug" => Ok(BinaryVersion::Debug),
+            "release" => Ok(BinaryVersion::Release),
+            _ => Err("Environment variable BINARY_VERSION only support \"debug\" and \"release\"."),
+        }
+    }
+}
+
+fn binary_version() -> BinaryVersion {
+    match env::var(BINARY_VERSION) {
+        Ok(val) => val.parse().expect("Binary version should be one of debug and release."),
+        Err(_) => BinaryVersion::Debug,
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BinaryError {
+    #[error("Can not find binary {name}, searched in:\n{}", format_paths(.searched))]
+    NotFound { name: String, searched: Vec<PathBuf> },
+    #[error("Can not read binary {name} from {}: {err}", .path.display())]
+    Read {
+        name: String,
+        path: PathBuf,
+        err: io::Error,
+    },
+}
+
+fn format_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("  - {}", path.display()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A binary loaded from disk.
+#[derive(Debug, Clone)]
+pub struct Binary {
+    pub name: String,
+    pub path: PathBuf,
+    pub data: bytes::Bytes,
+    pub data_hash: Byte32,
+    /// The binary is older than the source files of its contract or libs, so it is probably not rebuilt after
+    /// modifications.
+    pub is_stale: bool,
+}
+
+/// The root directory of the project, it does not depend on the current directory of the process.
+pub fn project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+/// The directories to search for a binary in order.
+///
+/// The directories in the environment variable BINARY_PATH come first, they are separated like PATH. Then there is
+/// build/<BINARY_VERSION> for contracts or deployed-scripts for deployed contracts.
+pub fn search_paths(is_deployed: bool) -> Vec<PathBuf> {
+    let mut paths = match env::var_os(BINARY_PATH) {
+        Some(val) => env::split_paths(&val).collect(),
+        None => vec![],
+    };
+
+    let root = project_root();
+    if is_deployed {
+        paths.push(root.join("deployed-scripts"));
+    } else {
+        let binary_dir = match binary_version() {
+            BinaryVersion::Debug => "debug",
+            BinaryVersion::Release => "release",
+        };
+        paths.push(root.join("build").join(binary_dir));
+    }
+
+    paths
+}
+
+/// Load the binary from the first search path containing it, the result is cached for the whole process.
+///
+/// A warning is printed if the binary is older than the source files of its contract or libs.
+pub fn load_binary(name: &str, is_deployed: bool) -> Result<Arc<Binary>, BinaryError> {
+    let searched = search_paths(is_deployed);
+    let path = searched
+        .iter()
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| BinaryError::NotFound {
+            name: name.to_string(),
+            searched: searched.clone(),
+        })?;
+
+    let mut cache = BINARY_CACHE.lock().unwrap();
+    if let Some(binary) = cache.get(&path) {
+        return Ok(binary.clone());
+    }
+
+    println!("Loading binary {} from {} ...", name, path.display());
+    let data: bytes::Bytes = fs::read(&path)
+        .map_err(|err| BinaryError::Read {
+            name: name.to_string(),
+            path: path.clone(),
+            err,
+        })?
+        .into();
+    let is_stale = !is_deployed && is_stale(&path, &source_dirs(name));
+    if is_stale {
+        println!(
+            "Warning: the binary {} is older than the source of contracts/{} or libs, it may need to be rebuilt.",
+            path.display(),
+            name
+        );
+    }
+
+    let binary = Arc::new(Binary {
+        name: name.to_string(),
+        path: path.clone(),
+        data_hash: CellOutput::calc_data_hash(&data),
+        data,
+        is_stale,
+    });
+    cache.insert(path, binary.clone());
+
+    Ok(binary)
+}
+
+/// The names of all known binaries, including the contracts in contracts/ which may not be built yet.
+pub fn binary_names() -> Vec<String> {
+    let root = project_root();
+    let mut dirs = search_paths(true);
+    dirs.extend(search_paths(false));
+    dirs.push(root.join("build").join("debug"));
+    dirs.push(root.join("build").join("release"));
+
+    let mut names = vec![];
+    for dir in dirs {
+        names.extend(list_dir(&dir, false));
+    }
+    // The binaries of contracts are named after their directories.
+    names.extend(list_dir(&root.join("contracts"), true));
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn list_dir(dir: &Path, is_dir: bool) -> Vec<String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|ty| ty.is_dir() == is_dir).unwrap_or(false))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect()
+}
+
+/// The directories whose modifications require the contract to be rebuilt, they are the contract itself and the shared
+/// libs. Deployed binaries have no source in contracts/, so they are never stale.
+fn source_dirs(name: &str) -> Vec<PathBuf> {
+    let root = project_root();
+    let contract_dir = root.join("contracts").join(name);
+    if !contract_dir.is_dir() {
+        return vec![];
+    }
+
+    vec![contract_dir, root.join("libs")]
+}
+
+fn is_stale(path: &Path, source_dirs: &[PathBuf]) -> bool {
+    let modified = match fs::metadata(path).and_then(|meta| meta.modified()) {
+        Ok(modified) => modified,
+        Err(_) => return false,
+    };
+
+    match source_dirs.iter().filter_map(|dir| latest_modified(dir)).max() {
+        Some(source_modified) => source_modified > modified,
+        None => false,
+    }
+}
+
+fn latest_modified(dir: &Path) -> Option<SystemTime> {
+    let mut latest = None;
+    for entry in fs::read_dir(dir).ok()?.filter_map(|entry| entry.ok()) {
+        let modified = match entry.file_type() {
+            Ok(ty) if ty.is_dir() && entry.file_name() != "target" => latest_modified(&entry.path()),
+            Ok(ty) if ty.is_file() => entry.metadata().and_then(|meta| meta.modified()).ok(),
+            _ => None,
+        };
+        latest = latest.max(modified);
+    }
+
+    latest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_binary() {
+        let binary = load_binary("always_success", true).unwrap();
+        assert_eq!(binary.data_hash, CellOutput::calc_data_hash(&binary.data));
+        assert!(!binary.is_stale);
+
+        // The binary is cached, so the same instance is returned.
+        let cached = load_binary("always_success", true).unwrap();
+        assert!(Arc::ptr_eq(&binary, &cached));
+
+        let err = load_binary("not-exist", true).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Can not find binary not-exist"));
+        assert!(message.contains("deployed-scripts"));
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let dir = env::temp_dir().join(format!("binary-registry-{}", std::process::id()));
+        let lib_dir = dir.join("libs");
+        fs::create_dir_all(&lib_dir).unwrap();
+        let binary_path = dir.join("binary");
+        let source_path = lib_dir.join("lib.rs");
+        let set_modified = |path: &Path, modified: SystemTime| {
+            fs::File::create(path).unwrap().set_modified(modified).unwrap();
+        };
+
+        let now = SystemTime::now();
+        set_modified(&binary_path, now);
+        set_modified(&source_path, now - std::time::Duration::from_secs(10));
+        assert!(!is_stale(&binary_path, std::slice::from_ref(&lib_dir)));
+
+        // Modifying the shared libs also makes the binary stale.
+        set_modified(&source_path, now + std::time::Duration::from_secs(10));
+        assert!(is_stale(&binary_path, &[dir.join("not-exist"), lib_dir.clone()]));
+        assert!(!is_stale(&binary_path, &[]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
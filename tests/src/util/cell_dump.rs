@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::fs;
+
+use ckb_jsonrpc_types as json_types;
+use ckb_mock_tx_types::{MockCellDep, MockInfo, MockInput, MockTransaction};
+use ckb_types::core::{DepType, HeaderView};
+use ckb_types::packed::{Byte32, CellDep, CellOutput, OutPoint, OutPointVec, Transaction};
+use ckb_types::prelude::*;
+use ckb_types::{bytes, H256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A local dump of live cells and headers, it is used to resolve transactions fetched from a real chain.
+///
+/// The dump file should be a JSON object like below, all fields are the same as the RPC of CKB node:
+///
+/// ```json
+/// {
+///     "cells": [
+///         {
+///             "out_point": { "tx_hash": "0x...", "index": "0x0" },
+///             "output": { "capacity": "0x...", "lock": { ... }, "type": null },
+///             "data": "0x...",
+///             "header": "0x..." // optional, the hash of the block which the cell is committed in
+///         },
+///         ...
+///     ],
+///     "headers": [
+///         { ... }, // The same as the result of RPC get_header
+///         ...
+///     ]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CellDump {
+    #[serde(default)]
+    pub cells: Vec<DumpedCell>,
+    #[serde(default)]
+    pub headers: Vec<json_types::HeaderView>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DumpedCell {
+    pub out_point: json_types::OutPoint,
+    pub output: json_types::CellOutput,
+    pub data: json_types::JsonBytes,
+    #[serde(default)]
+    pub header: Option<H256>,
+}
+
+impl CellDump {
+    pub fn from_file(filepath: &str) -> Result<Self, Box<dyn StdError>> {
+        let raw_json = fs::read_to_string(filepath)?;
+        Ok(serde_json::from_str(&raw_json)?)
+    }
+
+    pub fn get_cell(&self, out_point: &OutPoint) -> Option<(CellOutput, bytes::Bytes, Option<Byte32>)> {
+        self.cells
+            .iter()
+            .find(|cell| OutPoint::from(cell.out_point.clone()) == *out_point)
+            .map(|cell| {
+                (
+                    cell.output.clone().into(),
+                    cell.data.clone().into_bytes(),
+                    cell.header.as_ref().map(|hash| hash.pack()),
+                )
+            })
+    }
+
+    pub fn get_header(&self, hash: &Byte32) -> Option<HeaderView> {
+        self.headers
+            .iter()
+            .find(|header| header.hash.pack() == *hash)
+            .map(|header| header.clone().into())
+    }
+
+    /// Assemble a MockTransaction with the cells and headers in the dump, the cells of dep groups are included too.
+    pub fn to_mock_tx(&self, tx: Transaction) -> Result<MockTransaction, Box<dyn StdError>> {
+        let tx_view = tx.clone().into_view();
+        let mut mock_info = MockInfo::default();
+
+        for (i, input) in tx_view.inputs().into_iter().enumerate() {
+            let (output, data, header) = self.get_cell(&input.previous_output()).ok_or_else(|| {
+                format!(
+                    "The cell of inputs[{}] is missing in the dump: {}",
+                    i,
+                    input.previous_output()
+                )
+            })?;
+            mock_info.inputs.push(MockInput {
+                input,
+                output,
+                data,
+                header,
+            });
+        }
+
+        let mut seen = HashSet::new();
+        for (i, cell_dep) in tx_view.cell_deps().into_iter().enumerate() {
+            let (output, data, header) = self.get_cell(&cell_dep.out_point()).ok_or_else(|| {
+                format!(
+                    "The cell of cell_deps[{}] is missing in the dump: {}",
+                    i,
+                    cell_dep.out_point()
+                )
+            })?;
+
+            if cell_dep.dep_type() == DepType::DepGroup.into() {
+                let out_points = OutPointVec::from_slice(&data)
+                    .map_err(|err| format!("The data of cell_deps[{}] is not a valid dep group: {}", i, err))?;
+                for out_point in out_points.into_iter() {
+                    let (output, data, header) = self.get_cell(&out_point).ok_or_else(|| {
+                        format!(
+                            "The cell in dep group cell_deps[{}] is missing in the dump: {}",
+                            i, out_point
+                        )
+                    })?;
+                    if seen.insert(out_point.clone()) {
+                        mock_info.cell_deps.push(MockCellDep {
+                            cell_dep: CellDep::new_builder().out_point(out_point).build(),
+                            output,
+                            data,
+                            header,
+                        });
+                    }
+                }
+            }
+
+            if seen.insert(cell_dep.out_point()) {
+                mock_info.cell_deps.push(MockCellDep {
+                    cell_dep,
+                    output,
+                    data,
+                    header,
+                });
+            }
+        }
+
+        for hash in tx_view.header_deps().into_iter() {
+            let header = self
+                .get_header(&hash)
+                .ok_or_else(|| format!("The header {} in header_deps is missing in the dump", hash))?;
+            mock_info.header_deps.push(header);
+        }
+
+        Ok(MockTransaction { mock_info, tx })
+    }
+}
+
+/// Parse a transaction in the RPC format, the result of RPC get_transaction is also supported.
+pub fn parse_rpc_tx(raw_json: &str) -> Result<json_types::Transaction, Box<dyn StdError>> {
+    let mut value: Value = serde_json::from_str(raw_json)?;
+    if !value["transaction"].is_null() {
+        value = value["transaction"].take();
+    }
+    // The TransactionView contains an extra field hash, but Transaction does not allow unknown fields.
+    if let Some(object) = value.as_object_mut() {
+        object.remove("hash");
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
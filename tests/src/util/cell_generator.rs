@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+
+use super::type_id;
+
+lazy_static! {
+    /// Map the type ID name of a contract to the generator of its cells, they are registered by the tests of each
+    /// contract with `register_cell_generator`.
+    static ref CELL_GENERATORS: RwLock<HashMap<String, Arc<dyn CellGenerator>>> = RwLock::new(HashMap::new());
+}
+
+/// Turn the high-level description of a cell into a custom cell, which is then pushed by
+/// `TemplateGenerator::push_cell`.
+///
+/// The description is defined by each contract, the output should be like below:
+///
+/// ```json
+/// json!({
+///     "capacity": u64 | "142 CKB" | "auto",
+///     "lock": Script,
+///     "type": null | Script,
+///     "data": null | "0x...",
+///     "entity": null | "0x...", // the molecule encoded entity, it is wrapped in the envelope with the version
+///     "header": null | {...},
+///     "label": null | "..."
+/// })
+/// ```
+pub trait CellGenerator: Send + Sync {
+    /// The version is the version of the entity in the cell data, it is 1 if the caller does not specify.
+    fn gen_cell(&self, version: u32, cell: Value) -> Value;
+}
+
+/// The generator of contracts whose cells need no conversion, the description is already a custom cell.
+pub struct CustomCellGenerator;
+
+impl CellGenerator for CustomCellGenerator {
+    fn gen_cell(&self, _version: u32, cell: Value) -> Value {
+        cell
+    }
+}
+
+/// Register the generator of the contract, the previous one is replaced if the name is registered already.
+pub fn register_cell_generator(name: &str, generator: impl CellGenerator + 'static) {
+    CELL_GENERATORS
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(generator));
+}
+
+/// Find the generator of the contract, contracts without a registered generator fall back to CustomCellGenerator as
+/// long as the type ID of the contract is known.
+pub fn get_cell_generator(name: &str) -> Option<Arc<dyn CellGenerator>> {
+    if let Some(generator) = CELL_GENERATORS.read().unwrap().get(name) {
+        return Some(generator.clone());
+    }
+
+    type_id::get_type_id(name).map(|_| Arc::new(CustomCellGenerator) as Arc<dyn CellGenerator>)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_fallback_generator() {
+        // Contracts with a known type ID need no registration if their cells need no conversion.
+        let cell = json!({ "capacity": "auto", "lock": { "code_hash": "{{always-success}}" } });
+        let generator = get_cell_generator("playground").unwrap();
+        assert_eq!(generator.gen_cell(1, cell.clone()), cell);
+
+        assert!(get_cell_generator("not-exist").is_none());
+    }
+}
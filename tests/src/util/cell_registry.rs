@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use ckb_mock_tx_types::MockResourceLoader;
+use ckb_types::core::{HeaderView, TransactionView};
+use ckb_types::packed::{Byte32, CellOutput, OutPoint};
+use ckb_types::prelude::*;
+use ckb_types::{bytes, H256};
+use serde_json::Value;
+
+use super::cell_dump::CellDump;
+
+#[derive(Debug, Clone)]
+pub struct LiveCell {
+    pub output: CellOutput,
+    pub data: bytes::Bytes,
+    /// The hash of the block which the cell is committed in, it is None for cells created without a block.
+    pub header: Option<Byte32>,
+}
+
+/// A registry of live cells and headers, templates can reference its cells by out point or by label.
+///
+/// ```json
+/// { "out_point": { "tx_hash": "0x...", "index": 0 } }
+/// { "tmp_ref": "<label>" }
+/// ```
+///
+/// Cells are registered by MockChain when transactions are committed, the outputs with `tmp_label` in templates are
+/// labeled at the same time. They can also be preloaded manually or from a CellDump.
+#[derive(Debug, Clone, Default)]
+pub struct CellRegistry {
+    cells: HashMap<OutPoint, LiveCell>,
+    labels: HashMap<String, OutPoint>,
+    headers: HashMap<Byte32, HeaderView>,
+}
+
+impl CellRegistry {
+    pub fn new() -> Self {
+        CellRegistry::default()
+    }
+
+    pub fn from_cell_dump(dump: &CellDump) -> Self {
+        let mut registry = CellRegistry::new();
+        for cell in dump.cells.iter() {
+            registry.insert_cell(
+                cell.out_point.clone().into(),
+                cell.output.clone().into(),
+                cell.data.clone().into_bytes(),
+                cell.header.as_ref().map(|hash| hash.pack()),
+            );
+        }
+        for header in dump.headers.iter() {
+            registry.insert_header(header.clone().into());
+        }
+
+        registry
+    }
+
+    pub fn insert_cell(&mut self, out_point: OutPoint, output: CellOutput, data: bytes::Bytes, header: Option<Byte32>) {
+        self.cells.insert(out_point, LiveCell { output, data, header });
+    }
+
+    pub fn insert_header(&mut self, header: HeaderView) {
+        self.headers.insert(header.hash(), header);
+    }
+
+    /// Register all outputs of the transaction, and label them with the `tmp_label` field of outputs in the template.
+    pub fn insert_outputs(&mut self, tx: &TransactionView, template: &Value, header: Option<Byte32>) {
+        for (i, (out_point, (output, data))) in tx.output_pts().into_iter().zip(tx.outputs_with_data_iter()).enumerate()
+        {
+            if let Some(label) = template["outputs"][i]["tmp_label"].as_str() {
+                self.set_label(label, out_point.clone());
+            }
+            self.insert_cell(out_point, output, data, header.clone());
+        }
+    }
+
+    /// Bind the label to the out point, the label will point to the new out point if it is used again.
+    pub fn set_label(&mut self, label: &str, out_point: OutPoint) {
+        self.labels.insert(label.to_string(), out_point);
+    }
+
+    pub fn remove_cell(&mut self, out_point: &OutPoint) -> Option<LiveCell> {
+        self.cells.remove(out_point)
+    }
+
+    pub fn get_cell(&self, out_point: &OutPoint) -> Option<&LiveCell> {
+        self.cells.get(out_point)
+    }
+
+    pub fn get_label(&self, label: &str) -> Option<&OutPoint> {
+        self.labels.get(label)
+    }
+
+    pub fn get_header(&self, hash: &Byte32) -> Option<&HeaderView> {
+        self.headers.get(hash)
+    }
+}
+
+impl MockResourceLoader for CellRegistry {
+    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
+        Ok(self.headers.get(&hash.pack()).cloned())
+    }
+
+    fn get_live_cell(
+        &mut self,
+        out_point: OutPoint,
+    ) -> Result<Option<(CellOutput, bytes::Bytes, Option<Byte32>)>, String> {
+        Ok(self
+            .cells
+            .get(&out_point)
+            .map(|cell| (cell.output.clone(), cell.data.clone(), cell.header.clone())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ckb_types::core::TransactionBuilder;
+    use serde_json::json;
+
+    use super::*;
+
+    fn tx_with_outputs(capacities: &[u64]) -> TransactionView {
+        let outputs = capacities
+            .iter()
+            .map(|capacity| CellOutput::new_builder().capacity(capacity.pack()).build());
+        TransactionBuilder::default()
+            .outputs(outputs)
+            .outputs_data(capacities.iter().map(|_| bytes::Bytes::new().pack()))
+            .build()
+    }
+
+    #[test]
+    fn test_insert_outputs() {
+        let tx = tx_with_outputs(&[100, 200]);
+        let template = json!({
+            "outputs": [
+                { "capacity": 100 },
+                { "capacity": 200, "tmp_label": "change" }
+            ]
+        });
+
+        let mut registry = CellRegistry::new();
+        registry.insert_outputs(&tx, &template, None);
+
+        let out_point = registry.get_label("change").cloned().unwrap();
+        assert_eq!(out_point, OutPoint::new(tx.hash(), 1));
+        let cell = registry.get_cell(&out_point).unwrap();
+        assert_eq!(Unpack::<u64>::unpack(&cell.output.capacity()), 200);
+        // Unlabeled outputs are registered too.
+        assert!(registry.get_cell(&OutPoint::new(tx.hash(), 0)).is_some());
+        assert!(registry.get_label("unknown").is_none());
+
+        // The label points to the latest out point if it is used again.
+        let tx = tx_with_outputs(&[300]);
+        let template = json!({ "outputs": [{ "capacity": 300, "tmp_label": "change" }] });
+        registry.insert_outputs(&tx, &template, None);
+        assert_eq!(
+            registry.get_label("change").cloned().unwrap(),
+            OutPoint::new(tx.hash(), 0)
+        );
+
+        assert!(registry.remove_cell(&out_point).is_some());
+        assert!(registry.get_cell(&out_point).is_none());
+    }
+}
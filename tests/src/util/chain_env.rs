@@ -0,0 +1,149 @@
+use std::error::Error as StdError;
+
+use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder};
+use ckb_script::TxVerifyEnv;
+use ckb_types::core::hardfork::{HardForks, CKB2021, CKB2023};
+use ckb_types::core::{EpochNumber, HeaderView};
+use ckb_types::prelude::*;
+use serde_json::Value;
+
+use super::template_parser::TemplateParser;
+
+/// The environment which transactions are verified in, it decides which hardfork features and VM versions are active.
+///
+/// It can be configured by parser options or by the `tmp_env` field of templates like below:
+///
+/// ```json
+/// "tmp_env": {
+///     "tip": {
+///         ... // The same as the header_deps, the epoch is the full value like the RPC of CKB node
+///     },
+///     // The epoch when the hardfork is activated, true means activated from genesis and false means never.
+///     "hardforks": {
+///         "ckb2021": 0,
+///         "ckb2023": false
+///     },
+///     // A shortcut of hardforks, 0 disables both, 1 enables ckb2021 and 2 enables both from genesis.
+///     "vm_version": 1
+/// }
+/// ```
+///
+/// All fields are optional, by default the tip is the genesis block and hardforks are the same as mainnet, so only VM
+/// version 0 is available.
+#[derive(Debug, Clone)]
+pub struct ChainEnv {
+    pub tip: HeaderView,
+    pub hardforks: HardForks,
+}
+
+impl Default for ChainEnv {
+    fn default() -> Self {
+        ChainEnv {
+            tip: HeaderView::new_advanced_builder().epoch(0.pack()).build(),
+            hardforks: HardForks::new_mirana(),
+        }
+    }
+}
+
+impl ChainEnv {
+    pub fn with_vm_version(vm_version: u8) -> Self {
+        let mut env = ChainEnv::default();
+        env.set_vm_version(vm_version)
+            .expect("The VM version should be 0, 1 or 2.");
+        env
+    }
+
+    pub fn set_tip(&mut self, tip: HeaderView) {
+        self.tip = tip;
+    }
+
+    /// Activate the hardforks which the VM version requires from genesis, and disable the others.
+    pub fn set_vm_version(&mut self, vm_version: u8) -> Result<(), Box<dyn StdError>> {
+        let (ckb2021, ckb2023) = match vm_version {
+            0 => (EpochNumber::MAX, EpochNumber::MAX),
+            1 => (0, EpochNumber::MAX),
+            2 => (0, 0),
+            _ => return Err(format!("The VM version {} is not supported.", vm_version).into()),
+        };
+
+        self.hardforks = HardForks {
+            ckb2021: ckb2021_since(ckb2021),
+            ckb2023: CKB2023::new_with_specified(ckb2023),
+        };
+        Ok(())
+    }
+
+    /// Override the environment with the `tmp_env` field of templates, the omitted fields are kept.
+    pub fn apply_json(&mut self, field_name: &str, env: &Value) -> Result<(), Box<dyn StdError>> {
+        if !env["tip"].is_null() {
+            self.tip = TemplateParser::mock_block_header(&format!("{}.tip", field_name), &env["tip"])?;
+        }
+
+        match (&env["hardforks"], &env["vm_version"]) {
+            (Value::Null, Value::Null) => {}
+            (hardforks, Value::Null) => {
+                if !hardforks["ckb2021"].is_null() {
+                    let epoch =
+                        parse_activation_epoch(&format!("{}.hardforks.ckb2021", field_name), &hardforks["ckb2021"])?;
+                    self.hardforks.ckb2021 = ckb2021_since(epoch);
+                }
+                if !hardforks["ckb2023"].is_null() {
+                    let epoch =
+                        parse_activation_epoch(&format!("{}.hardforks.ckb2023", field_name), &hardforks["ckb2023"])?;
+                    self.hardforks.ckb2023 = CKB2023::new_with_specified(epoch);
+                }
+            }
+            (Value::Null, vm_version) => {
+                let vm_version = vm_version
+                    .as_u64()
+                    .ok_or_else(|| format!("Field `{}.vm_version` should be a number.", field_name))?;
+                self.set_vm_version(vm_version as u8)
+                    .map_err(|err| format!("Field `{}.vm_version` parse failed: {}", field_name, err))?;
+            }
+            _ => {
+                return Err(format!(
+                    "Field `{}.hardforks` and `{}.vm_version` can not be used together.",
+                    field_name, field_name
+                )
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn consensus(&self) -> Consensus {
+        ConsensusBuilder::default()
+            .hardfork_switch(self.hardforks.clone())
+            .build()
+    }
+
+    /// The transaction is verified as if it is submitted to the tx-pool when the tip is the current one.
+    pub fn tx_verify_env(&self) -> TxVerifyEnv {
+        TxVerifyEnv::new_submit(&self.tip)
+    }
+}
+
+fn ckb2021_since(epoch: EpochNumber) -> CKB2021 {
+    CKB2021::new_builder()
+        .rfc_0028(epoch)
+        .rfc_0029(epoch)
+        .rfc_0030(epoch)
+        .rfc_0031(epoch)
+        .rfc_0032(epoch)
+        .rfc_0036(epoch)
+        .rfc_0038(epoch)
+        .build()
+        .expect("All features of ckb2021 should be set.")
+}
+
+fn parse_activation_epoch(field_name: &str, field: &Value) -> Result<EpochNumber, Box<dyn StdError>> {
+    match field {
+        Value::Bool(true) => Ok(0),
+        Value::Bool(false) => Ok(EpochNumber::MAX),
+        Value::Number(val) => val
+            .as_u64()
+            .ok_or_else(|| format!("Field `{}` should be an epoch number.", field_name).into()),
+        _ => Err(format!("Field `{}` should be an epoch number or a boolean.", field_name).into()),
+    }
+}
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use ckb_types::{h256, H256};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -52,39 +50,18 @@ pub enum Source {
 pub enum ScriptHashType {
     Data = 0,
     Type = 1,
+    Data1 = 2,
+    Data2 = 4,
 }
 
 lazy_static! {
-    pub static ref TYPE_ID_TABLE: HashMap<&'static str, &'static str> = {
-        // For calculation of these type ID, you need uncomment a line of debug code in the funtion **mock_contract** in src/util/template_parser .
-        //
-        // CAREFUL! There may be some error in the map, but the contracts will still work. It is because when parsing scripts in cell_deps, their type
-        // ID will be calculated dynamically and insert into the map.
-        let mut map = HashMap::new();
-        // fake locks
-        map.insert(
-            "fake-das-lock",
-            "0xebd2ca43797df1eae21f5a0d20a09a3851beab063ca06d7b86a1e1e8ef9c7698",
-        );
-        map.insert(
-            "fake-secp256k1-blake160-signhash-all",
-            "0x8f2d7cb06512f2777207461d100b0562b0213232a1bd70261e57f37fdc61483d",
-        );
-        map.insert(
-            "always_success",
-            "0x34f052fc455fce7c71f4905f223653a5fbe64261c6b2537124de00f1d52820e9",
-        );
-        map.insert(
-            "always-success",
-            "0x610b14e8060fca49a46606bf2eaaa01f77a77daf27c22a3bec3cd13c6ceb1a60",
-        );
-        // types
-        map.insert(
-            "playground",
-            "0xca4d966895b1467702bad4038396b037d8c8f045cae9cf5a7db4eadefa347887",
-        );
-        map
-    };
     pub static ref RE_VARIABLE: Regex = Regex::new(r"\{\{([\w\-\.]+)\}\}").unwrap();
+    // Variables like {{data_hash:xxx}} are replaced with the blake2b hash of the binary, so the script can be referenced
+    // with hash_type data, data1 or data2.
+    pub static ref RE_DATA_HASH_VARIABLE: Regex = Regex::new(r"\{\{data_hash:([\w\-\.]+)\}\}").unwrap();
+    // Relative timestamps like "+30d" or "-1h", they are offsets from TIMESTAMP.
+    pub static ref RE_RELATIVE_TIME: Regex = Regex::new(r"^([+-])(\d[\d_]*)([smhdy])$").unwrap();
+    // Capacity literals like "142 CKB", "1.5 CKB" or "100 shannon".
+    pub static ref RE_CAPACITY: Regex = Regex::new(r"^(\d[\d_]*)(?:\.(\d+))?\s*(CKB|ckb|shannon|shannons)$").unwrap();
     pub static ref RE_ZH_CHAR: Regex = Regex::new(r"^[\u4E00-\u9FA5]+$").unwrap();
 }
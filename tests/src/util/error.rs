@@ -1 +1,404 @@
+use std::fmt;
+use std::path::Path;
 
+use ckb_script::{ScriptError, ScriptGroupType};
+use ckb_types::packed::Byte32;
+use thiserror::Error;
+
+use super::constants::Source;
+use super::tx_lint::LintIssue;
+
+#[derive(Debug, Error)]
+pub enum ExecuteError {
+    #[error("Resolve transaction error: {0}")]
+    Resolve(String),
+    #[error("Verify script error:\n{0}")]
+    Script(Box<ScriptFailure>),
+    #[error("Lint transaction error:\n{}", format_lint_issues(.0))]
+    Lint(Vec<LintIssue>),
+}
+
+fn format_lint_issues(issues: &[LintIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| format!("  - {}", issue))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The details of the script group which failed when executing a transaction.
+#[derive(Debug, Clone)]
+pub struct ScriptFailure {
+    /// The name of the script in the type ID map, it is None if the code_hash is unknown.
+    pub script_name: Option<String>,
+    pub script_hash: Byte32,
+    pub group_type: ScriptGroupType,
+    pub input_indices: Vec<usize>,
+    pub output_indices: Vec<usize>,
+    /// The exit code of the script, it is None if the script did not exit by itself, like exceeding the cycles limit.
+    pub exit_code: Option<i8>,
+    /// The exit code resolved with the error enum of the script, it is None if the exit code is None.
+    pub error: Option<ErrorCode>,
+    /// The message printed by the panic handler of ckb-std if the script panicked.
+    pub panic_message: Option<String>,
+    pub cause: ScriptError,
+}
+
+impl ScriptFailure {
+    pub fn new(
+        script_name: Option<String>,
+        script_hash: Byte32,
+        group_type: ScriptGroupType,
+        input_indices: Vec<usize>,
+        output_indices: Vec<usize>,
+        cause: ScriptError,
+    ) -> Self {
+        let exit_code = match cause {
+            ScriptError::ValidationFailure(_, code) => Some(code),
+            _ => None,
+        };
+        let error = exit_code.map(|code| match &script_name {
+            Some(name) => ErrorCode::resolve(name, code),
+            None => ErrorCode::from(code),
+        });
+
+        ScriptFailure {
+            script_name,
+            script_hash,
+            group_type,
+            input_indices,
+            output_indices,
+            exit_code,
+            error,
+            panic_message: None,
+            cause,
+        }
+    }
+
+    pub fn is_lock(&self) -> bool {
+        self.group_type == ScriptGroupType::Lock
+    }
+
+    pub fn is_type(&self) -> bool {
+        self.group_type == ScriptGroupType::Type
+    }
+
+    /// Generate the ckb-debugger command which runs this script group with the dumped mock transaction.
+    pub fn debugger_command(&self, tx_file: &Path) -> String {
+        let group_type = match self.group_type {
+            ScriptGroupType::Lock => "lock",
+            ScriptGroupType::Type => "type",
+        };
+        let (cell_type, cell_index) = match (self.input_indices.first(), self.output_indices.first()) {
+            (Some(index), _) => ("input", *index),
+            (None, Some(index)) => ("output", *index),
+            (None, None) => ("input", 0),
+        };
+
+        format!(
+            "ckb-debugger --tx-file {} --script-group-type {} --cell-type {} --cell-index {}",
+            tx_file.display(),
+            group_type,
+            cell_type,
+            cell_index
+        )
+    }
+}
+
+impl fmt::Display for ScriptFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "      Script: {} (0x{:x})",
+            self.script_name.as_deref().unwrap_or("unknown"),
+            self.script_hash
+        )?;
+        writeln!(
+            f,
+            "       Group: {}, inputs: {:?}, outputs: {:?}",
+            self.group_type, self.input_indices, self.output_indices
+        )?;
+        match &self.error {
+            Some(error) => writeln!(f, "       Error: {}", error)?,
+            None => writeln!(f, "       Error: -")?,
+        }
+        if let Some(msg) = &self.panic_message {
+            writeln!(f, "       Panic: {}", msg)?;
+        }
+        write!(f, "       Cause: {}", self.cause)
+    }
+}
+
+/// Describe which script and which cell a transaction is expected to fail at, every field is optional.
+///
+/// For example `ExpectedFailure::type_("playground").at(Source::Input, 0)` means the type script playground should
+/// fail and its script group should contain inputs[0].
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedFailure {
+    pub script: Option<String>,
+    pub group_type: Option<ScriptGroupType>,
+    pub cell: Option<(Source, usize)>,
+}
+
+impl ExpectedFailure {
+    pub fn lock(script: &str) -> Self {
+        ExpectedFailure {
+            script: Some(script.to_string()),
+            group_type: Some(ScriptGroupType::Lock),
+            cell: None,
+        }
+    }
+
+    pub fn type_(script: &str) -> Self {
+        ExpectedFailure {
+            script: Some(script.to_string()),
+            group_type: Some(ScriptGroupType::Type),
+            cell: None,
+        }
+    }
+
+    pub fn at(mut self, source: Source, index: usize) -> Self {
+        self.cell = Some((source, index));
+        self
+    }
+
+    /// Compare with the real failure and return the description of every mismatched field.
+    pub fn mismatches(&self, failure: &ScriptFailure, expected_error: &ErrorCode) -> Vec<String> {
+        let mut ret = vec![];
+
+        if failure.exit_code != Some(expected_error.code) {
+            let got = failure
+                .error
+                .map(|error| error.to_string())
+                .unwrap_or_else(|| String::from("no exit code"));
+            ret.push(format!("expected error {}, but got {}", expected_error, got));
+        }
+        if let Some(contract) = expected_error.contract {
+            if failure.script_name.as_deref() != Some(contract) {
+                ret.push(format!(
+                    "expected error of script {}, but got {:?}",
+                    contract, failure.script_name
+                ));
+            }
+        }
+        if let Some(script) = &self.script {
+            if failure.script_name.as_ref() != Some(script) {
+                ret.push(format!("expected script {}, but got {:?}", script, failure.script_name));
+            }
+        }
+        if let Some(group_type) = self.group_type {
+            if failure.group_type != group_type {
+                ret.push(format!(
+                    "expected {} script, but got {} script",
+                    group_type, failure.group_type
+                ));
+            }
+        }
+        if let Some((source, index)) = self.cell {
+            let found = match source {
+                Source::Input => failure.input_indices.contains(&index),
+                Source::Output => failure.output_indices.contains(&index),
+                Source::CellDep => false,
+            };
+            if !found {
+                ret.push(format!(
+                    "expected the failed script group to contain {:?}[{}], but its inputs are {:?} and outputs are {:?}",
+                    source, index, failure.input_indices, failure.output_indices
+                ));
+            }
+        }
+
+        ret
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/script_errors.rs"));
+
+/// An error code of a contract, the contract and the variant name are optional because raw codes are also allowed.
+///
+/// The error enums of contracts are collected from contracts/*/src/error.rs by build.rs, they are modules named after
+/// the contracts here, like `error::playground::ScriptError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub contract: Option<&'static str>,
+    pub name: Option<&'static str>,
+    pub code: i8,
+}
+
+impl ErrorCode {
+    /// Find the error code by the contract name and the variant name.
+    pub fn find(contract: &str, name: &str) -> Option<Self> {
+        SCRIPT_ERRORS
+            .iter()
+            .find(|(item, _)| *item == contract)
+            .and_then(|(contract, variants)| {
+                variants
+                    .iter()
+                    .find(|(item, _)| *item == name)
+                    .map(|(name, code)| ErrorCode {
+                        contract: Some(contract),
+                        name: Some(name),
+                        code: *code,
+                    })
+            })
+    }
+
+    /// Resolve the variant name of the code, the name will be None if the contract or the code is unknown.
+    pub fn resolve(contract: &str, code: i8) -> Self {
+        match SCRIPT_ERRORS.iter().find(|(item, _)| *item == contract) {
+            Some((contract, variants)) => ErrorCode {
+                contract: Some(contract),
+                name: variants.iter().find(|(_, item)| *item == code).map(|(name, _)| *name),
+                code,
+            },
+            None => ErrorCode {
+                contract: None,
+                name: None,
+                code,
+            },
+        }
+    }
+}
+
+impl From<i8> for ErrorCode {
+    fn from(code: i8) -> Self {
+        ErrorCode {
+            contract: None,
+            name: None,
+            code,
+        }
+    }
+}
+
+impl From<(&str, &str)> for ErrorCode {
+    fn from((contract, name): (&str, &str)) -> Self {
+        match ErrorCode::find(contract, name) {
+            Some(val) => val,
+            None => panic!("Can not find error {} of contract {}", name, contract),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(contract) = self.contract {
+            write!(f, "{}: ", contract)?;
+        }
+        match self.name {
+            Some(name) => write!(f, "{}({})", name, self.code),
+            None => write!(f, "{}", self.code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playground_failure(group_type: ScriptGroupType, code: i8) -> ScriptFailure {
+        ScriptFailure::new(
+            Some(String::from("playground")),
+            Byte32::default(),
+            group_type,
+            vec![0],
+            vec![1],
+            ScriptError::ValidationFailure(String::new(), code),
+        )
+    }
+
+    #[test]
+    fn test_failure_matched() {
+        let failure = playground_failure(ScriptGroupType::Type, playground::ScriptError::ItemMissing as i8);
+        let expected = ExpectedFailure::type_("playground").at(Source::Output, 1);
+
+        assert_eq!(
+            expected.mismatches(&failure, &playground::ScriptError::ItemMissing.into()),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_failure_mismatched_code() {
+        let failure = playground_failure(ScriptGroupType::Type, playground::ScriptError::ItemMissing as i8);
+        let expected = ExpectedFailure::type_("playground");
+
+        let mismatches = expected.mismatches(&failure, &playground::ScriptError::Encoding.into());
+        assert_eq!(
+            mismatches,
+            vec![String::from(
+                "expected error playground: Encoding(4), but got playground: ItemMissing(2)"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_failure_mismatched_group_type() {
+        let failure = playground_failure(ScriptGroupType::Type, playground::ScriptError::ItemMissing as i8);
+        let expected = ExpectedFailure::lock("playground");
+
+        let mismatches = expected.mismatches(&failure, &playground::ScriptError::ItemMissing.into());
+        assert_eq!(
+            mismatches,
+            vec![String::from("expected Lock script, but got Type script")]
+        );
+    }
+
+    #[test]
+    fn test_failure_mismatched_cell() {
+        let failure = playground_failure(ScriptGroupType::Type, playground::ScriptError::ItemMissing as i8);
+        let expected = ExpectedFailure::type_("playground").at(Source::Input, 1);
+
+        let mismatches = expected.mismatches(&failure, &playground::ScriptError::ItemMissing.into());
+        assert_eq!(
+            mismatches,
+            vec![String::from(
+                "expected the failed script group to contain Input[1], but its inputs are [0] and outputs are [1]"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_failure_mismatched_script() {
+        let failure = ScriptFailure::new(
+            Some(String::from("always-success")),
+            Byte32::default(),
+            ScriptGroupType::Lock,
+            vec![0],
+            vec![],
+            ScriptError::ValidationFailure(String::new(), 2),
+        );
+        let expected = ExpectedFailure::lock("playground");
+
+        let mismatches = expected.mismatches(&failure, &playground::ScriptError::ItemMissing.into());
+        assert_eq!(
+            mismatches,
+            vec![
+                String::from("expected error of script playground, but got Some(\"always-success\")"),
+                String::from("expected script playground, but got Some(\"always-success\")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_code() {
+        let error = ErrorCode::from(-31);
+        assert_eq!(error.contract, None);
+        assert_eq!(error.name, None);
+        assert_eq!(error.to_string(), "-31");
+
+        let error = ErrorCode::from(("spawn-caller", "InvalidArgs"));
+        assert_eq!(error, ErrorCode::from(spawn_caller::ScriptError::InvalidArgs));
+        assert_eq!(error.to_string(), "spawn-caller: InvalidArgs(5)");
+
+        assert_eq!(ErrorCode::resolve("spawn-caller", 5), error);
+        // The contract is known but the code is not.
+        assert_eq!(ErrorCode::resolve("playground", 100).to_string(), "playground: 100");
+        assert_eq!(ErrorCode::resolve("unknown", 5), ErrorCode::from(5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_error_code_not_found() {
+        let _ = ErrorCode::from(("playground", "Unknown"));
+    }
+}
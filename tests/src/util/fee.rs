@@ -0,0 +1,112 @@
+use std::{env, fmt};
+
+use ckb_types::core::tx_pool::get_transaction_weight;
+use ckb_types::core::{Cycle, FeeRate, TransactionView};
+use ckb_types::prelude::*;
+
+const FEE_RATE: &str = "FEE_RATE";
+// The default min_fee_rate of the tx-pool of CKB node in shannons/KW.
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+/// The fee rate is decided by the environment variable FEE_RATE in shannons/KW, it is 1000 by default.
+pub fn fee_rate() -> u64 {
+    match env::var(FEE_RATE) {
+        Ok(val) => val
+            .replace('_', "")
+            .parse()
+            .expect("Environment variable FEE_RATE should be a number in shannons/KW."),
+        Err(_) => DEFAULT_FEE_RATE,
+    }
+}
+
+/// Calculate the minimal fee which the tx-pool accepts.
+///
+/// The size is the serialized size of the transaction in a block, and the cycles are converted to weight like the
+/// tx-pool, so the weight is the larger one of them.
+pub fn min_fee(tx_size: usize, cycles: Cycle, fee_rate: u64) -> u64 {
+    let weight = get_transaction_weight(tx_size, cycles);
+    FeeRate::from_u64(fee_rate).fee(weight).as_u64()
+}
+
+/// The fee of an executed transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxFee {
+    /// The serialized size of the transaction in a block, it contains 4 bytes for the offset in the block.
+    pub size: usize,
+    pub cycles: Cycle,
+    pub weight: u64,
+    /// The fee rate in shannons/KW.
+    pub fee_rate: u64,
+    pub min_fee: u64,
+    /// The capacity of inputs minus outputs, it is None if outputs are more than inputs like withdrawing NervosDAO.
+    pub paid_fee: Option<u64>,
+}
+
+impl TxFee {
+    pub fn new(tx: &TransactionView, inputs_capacity: u64, cycles: Cycle, fee_rate: u64) -> Self {
+        let size = tx.data().serialized_size_in_block();
+        let outputs_capacity = tx
+            .outputs()
+            .into_iter()
+            .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+            .sum::<u64>();
+
+        TxFee {
+            size,
+            cycles,
+            weight: get_transaction_weight(size, cycles),
+            fee_rate,
+            min_fee: min_fee(size, cycles, fee_rate),
+            paid_fee: inputs_capacity.checked_sub(outputs_capacity),
+        }
+    }
+
+    pub fn is_enough(&self) -> bool {
+        self.paid_fee.map(|fee| fee >= self.min_fee).unwrap_or(false)
+    }
+
+    pub fn assert_enough(&self) {
+        assert!(
+            self.is_enough(),
+            "\n======\nThe transaction should pay at least {} shannons of fee, but it paid {}.\n======\n",
+            self.min_fee,
+            self.paid_fee
+                .map(|fee| fee.to_string())
+                .unwrap_or_else(|| String::from("nothing"))
+        );
+    }
+}
+
+impl fmt::Display for TxFee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let paid_fee = match self.paid_fee {
+            Some(fee) => format!("{} shannon", fee),
+            None => String::from("none"),
+        };
+        write!(
+            f,
+            r#"Transaction size: {} bytes,
+          Cycles: {},
+          Weight: {},
+         Min fee: {} shannon(feeRate: {} shannons/KW),
+        Paid fee: {}"#,
+            self.size, self.cycles, self.weight, self.min_fee, self.fee_rate, paid_fee
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_min_fee() {
+        // The size is larger than the weight of cycles.
+        assert_eq!(min_fee(1_000, 1_000_000, 1000), 1_000);
+        assert_eq!(min_fee(1_000, 1_000_000, 3000), 3_000);
+        // The weight of 10M cycles is 1705 bytes.
+        assert_eq!(min_fee(1_000, 10_000_000, 1000), 1_705);
+        // Round down like the tx-pool.
+        assert_eq!(min_fee(1_999, 0, 500), 999);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+use std::error::Error as StdError;
+
+use ckb_types::bytes;
+use ckb_types::core::hardfork::HardForks;
+use ckb_types::core::{EpochNumberWithFraction, HeaderView, TransactionView};
+use ckb_types::packed::{Byte32, CellOutput, OutPoint};
+use ckb_types::prelude::*;
+use serde_json::{json, Value};
+
+use super::cell_registry::{CellRegistry, LiveCell};
+use super::chain_env::ChainEnv;
+use super::constants::*;
+use super::error::{ErrorCode, ExpectedFailure};
+use super::out_point::{OutPointAllocator, OutPointSpace};
+use super::template_parser::{self, TemplateParser};
+
+// The same as the epoch length of mainnet in most of the time.
+pub const EPOCH_LENGTH: u64 = 1800;
+// The average block interval of mainnet in seconds.
+pub const BLOCK_INTERVAL: u64 = 8;
+
+/// An in-memory chain which keeps a live cell set and a list of blocks, it makes multi-transaction scenarios like
+/// create → update → destroy testable.
+///
+/// Every transaction passed the test is committed in a new block, then its outputs become live cells which can be
+/// spent by their real out points or labels in later templates, and the cells it consumed become dead. Only the cells
+/// known by the chain are tracked, so mock cells generated by templates can still be mixed in the same transaction.
+///
+/// ```json
+/// let mut chain = MockChain::new();
+/// // The outputs[0] is pushed with "label": "record"
+/// chain.test_tx(template.as_json());
+///
+/// let mut template = init("update");
+/// template.push_ref_input("record", None);
+/// ...
+/// chain.test_tx(template.as_json());
+/// ```
+pub struct MockChain {
+    registry: CellRegistry,
+    dead_cells: HashSet<OutPoint>,
+    blocks: Vec<HeaderView>,
+    // The timestamp increased by each block in seconds.
+    block_interval: u64,
+    hardforks: HardForks,
+    out_points: OutPointAllocator,
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        MockChain::new()
+    }
+}
+
+impl MockChain {
+    /// Create a chain whose tip is at HEIGHT and TIMESTAMP.
+    pub fn new() -> Self {
+        MockChain::with_tip(HEIGHT, TIMESTAMP)
+    }
+
+    /// Create a chain whose tip is at the given height and timestamp, the timestamp is in seconds.
+    pub fn with_tip(number: u64, timestamp: u64) -> Self {
+        let tip = MockChain::mock_header(number, timestamp * 1000, Byte32::default());
+        let mut registry = CellRegistry::new();
+        registry.insert_header(tip.clone());
+
+        MockChain {
+            registry,
+            dead_cells: HashSet::new(),
+            blocks: vec![tip],
+            block_interval: BLOCK_INTERVAL,
+            hardforks: ChainEnv::default().hardforks,
+            out_points: OutPointAllocator::new(),
+        }
+    }
+
+    pub fn set_block_interval(&mut self, seconds: u64) {
+        self.block_interval = seconds;
+    }
+
+    pub fn set_hardforks(&mut self, hardforks: HardForks) {
+        self.hardforks = hardforks;
+    }
+
+    /// Activate the hardforks which the VM version requires from genesis, and disable the others.
+    pub fn set_vm_version(&mut self, vm_version: u8) {
+        self.hardforks = ChainEnv::with_vm_version(vm_version).hardforks;
+    }
+
+    /// The environment of the tip, transactions executed on the chain are verified in it.
+    pub fn env(&self) -> ChainEnv {
+        ChainEnv {
+            tip: self.tip().clone(),
+            hardforks: self.hardforks.clone(),
+        }
+    }
+
+    pub fn tip(&self) -> &HeaderView {
+        self.blocks.last().expect("The chain should always have a tip block.")
+    }
+
+    /// The tip header in the template format, it can be pushed into header_deps directly.
+    ///
+    /// Be careful that the timestamp is in milliseconds like the real block header.
+    pub fn tip_json(&self) -> Value {
+        header_to_json(self.tip())
+    }
+
+    /// The live cells and headers of the chain, templates executed on the chain are resolved with it.
+    pub fn registry(&self) -> &CellRegistry {
+        &self.registry
+    }
+
+    pub fn get_header(&self, hash: &Byte32) -> Option<&HeaderView> {
+        self.registry.get_header(hash)
+    }
+
+    pub fn get_live_cell(&self, out_point: &OutPoint) -> Option<&LiveCell> {
+        self.registry.get_cell(out_point)
+    }
+
+    pub fn get_label(&self, label: &str) -> Option<&OutPoint> {
+        self.registry.get_label(label)
+    }
+
+    pub fn is_dead(&self, out_point: &OutPoint) -> bool {
+        self.dead_cells.contains(out_point)
+    }
+
+    /// Generate empty blocks, so the height, epoch and timestamp of the tip will advance.
+    pub fn advance(&mut self, blocks: u64) {
+        for _ in 0..blocks {
+            self.push_block(Byte32::default());
+        }
+    }
+
+    /// Add a live cell without any transaction, it is useful for preparing cells that should exist before the test.
+    pub fn insert_cell(&mut self, out_point: OutPoint, output: CellOutput, data: bytes::Bytes) {
+        let block_hash = self.tip().hash();
+        self.registry.insert_cell(out_point, output, data, Some(block_hash));
+    }
+
+    /// Like insert_cell, the out point is allocated in order, so it is the same between runs.
+    pub fn insert_mock_cell(&mut self, output: CellOutput, data: bytes::Bytes) -> OutPoint {
+        let out_point = self.out_points.alloc(OutPointSpace::Output);
+        self.insert_cell(out_point.clone(), output, data);
+        out_point
+    }
+
+    /// Bind a label to the live cell, then it can be referenced by `{ "tmp_ref": "<label>" }` in templates.
+    pub fn set_label(&mut self, label: &str, out_point: OutPoint) {
+        self.registry.set_label(label, out_point);
+    }
+
+    /// Commit the transaction in a new block, its inputs known by the chain become dead and its outputs become live.
+    ///
+    /// Return an error if any input has been spent, and nothing will be changed in that case.
+    pub fn commit_tx(&mut self, tx: &TransactionView) -> Result<HeaderView, Box<dyn StdError>> {
+        self.commit_tx_with_labels(tx, &Value::Null)
+    }
+
+    /// Like commit_tx, the outputs are also labeled with the `tmp_label` field of outputs in the template.
+    pub fn commit_tx_with_labels(
+        &mut self,
+        tx: &TransactionView,
+        template: &Value,
+    ) -> Result<HeaderView, Box<dyn StdError>> {
+        for (i, out_point) in tx.input_pts_iter().enumerate() {
+            if self.dead_cells.contains(&out_point) {
+                return Err(format!("The cell of inputs[{}] has been spent: {}", i, out_point).into());
+            }
+        }
+
+        let header = self.push_block(tx.hash());
+        for out_point in tx.input_pts_iter() {
+            if self.registry.remove_cell(&out_point).is_some() {
+                self.dead_cells.insert(out_point);
+            }
+        }
+        self.registry.insert_outputs(tx, template, Some(header.hash()));
+
+        Ok(header)
+    }
+
+    /// Execute the transaction like template_parser::test_tx, then commit it to the chain.
+    ///
+    /// The cells referenced by `out_point` or `tmp_ref` in the template are resolved with the live cells of the chain,
+    /// and the transaction is verified in the environment of the tip.
+    pub fn test_tx(&mut self, tx: Value) -> TransactionView {
+        let parser = TemplateParser::from_data(tx.clone(), 350_000_000)
+            .with_registry(self.registry.clone())
+            .with_env(self.env());
+        let tx_view = template_parser::test_tx_with_parser(parser);
+        if let Err(err) = self.commit_tx_with_labels(&tx_view, &tx) {
+            panic!(
+                "\n======\nThe transaction passed the test, but it can not be committed: {}\n======\n",
+                err
+            );
+        }
+
+        tx_view
+    }
+
+    /// Execute the transaction like template_parser::challenge_tx, the chain will not be changed.
+    pub fn challenge_tx(&self, tx: Value, expected_error: impl Into<ErrorCode>) {
+        self.challenge_tx_with(tx, expected_error, ExpectedFailure::default())
+    }
+
+    pub fn challenge_tx_with(&self, tx: Value, expected_error: impl Into<ErrorCode>, expected: ExpectedFailure) {
+        let parser = TemplateParser::from_data(tx, 350_000_000)
+            .with_registry(self.registry.clone())
+            .with_env(self.env());
+        template_parser::challenge_tx_with_parser(parser, expected_error, expected)
+    }
+
+    /// Generate an input in the template format which spends the live cell by its out point.
+    pub fn live_input(&self, out_point: &OutPoint, since_opt: Option<u64>) -> Value {
+        json!({
+            "previous_output": self.live_cell_dep(out_point),
+            "since": since_opt.unwrap_or(0)
+        })
+    }
+
+    /// Generate a cell dep in the template format which references the live cell by its out point.
+    pub fn live_cell_dep(&self, out_point: &OutPoint) -> Value {
+        if self.dead_cells.contains(out_point) {
+            panic!("The cell {} has been spent.", out_point);
+        }
+        if self.registry.get_cell(out_point).is_none() {
+            panic!("The cell {} is not a live cell of the chain.", out_point);
+        }
+
+        json!({
+            "out_point": {
+                "tx_hash": format!("0x{:x}", out_point.tx_hash()),
+                "index": Unpack::<u32>::unpack(&out_point.index())
+            }
+        })
+    }
+
+    fn push_block(&mut self, transactions_root: Byte32) -> HeaderView {
+        let tip = self.tip();
+        let header = MockChain::mock_header(
+            tip.number() + 1,
+            tip.timestamp() + self.block_interval * 1000,
+            transactions_root,
+        );
+        self.blocks.push(header.clone());
+        self.registry.insert_header(header.clone());
+
+        header
+    }
+
+    // Build headers from the template format, so they have the same hash as the headers parsed from templates.
+    fn mock_header(number: u64, timestamp: u64, transactions_root: Byte32) -> HeaderView {
+        let epoch = EpochNumberWithFraction::new(number / EPOCH_LENGTH, number % EPOCH_LENGTH, EPOCH_LENGTH);
+        let header = json!({
+            "number": number,
+            "timestamp": timestamp,
+            "epoch": epoch.full_value(),
+            "transactions_root": format!("0x{:x}", transactions_root)
+        });
+
+        TemplateParser::mock_block_header("block", &header).expect("The header of mock chain should be valid.")
+    }
+}
+
+fn header_to_json(header: &HeaderView) -> Value {
+    json!({
+        "version": header.version(),
+        "number": header.number(),
+        "timestamp": header.timestamp(),
+        "epoch": header.epoch().full_value(),
+        "transactions_root": format!("0x{:x}", header.transactions_root())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::template_generator::{ContractType, TemplateGenerator};
+
+    fn init() -> TemplateGenerator {
+        let mut template = TemplateGenerator::new("mock_chain", None);
+        template.push_contract_cell("always_success", ContractType::DeployedContract);
+        template
+    }
+
+    fn push_always_success_output(template: &mut TemplateGenerator, capacity: u64, label: &str) {
+        template.push_output(
+            json!({
+                "capacity": capacity,
+                "lock": { "code_hash": "{{always_success}}" },
+                "label": label
+            }),
+            None,
+        );
+    }
+
+    #[test]
+    fn test_cell_lifecycle() {
+        let mut chain = MockChain::new();
+
+        // create
+        let mut template = init();
+        template.push_input(
+            json!({
+                "capacity": 200 * ONE_CKB,
+                "lock": { "code_hash": "{{always_success}}" }
+            }),
+            None,
+            None,
+        );
+        push_always_success_output(&mut template, 200 * ONE_CKB, "record");
+        template.push_empty_witness();
+        let create_tx = chain.test_tx(template.as_json());
+        let out_point = create_tx.output_pts()[0].clone();
+        assert!(chain.get_live_cell(&out_point).is_some());
+        assert_eq!(chain.get_label("record"), Some(&out_point));
+        assert_eq!(chain.tip().number(), HEIGHT + 1);
+
+        // update
+        chain.advance(10);
+        let mut template = init();
+        template.push_ref_input("record", None);
+        push_always_success_output(&mut template, 199 * ONE_CKB, "record");
+        template.push_empty_witness();
+        let update_tx = chain.test_tx(template.as_json());
+        assert!(chain.is_dead(&out_point));
+        assert_eq!(chain.get_label("record"), Some(&update_tx.output_pts()[0]));
+        assert_eq!(chain.tip().number(), HEIGHT + 12);
+        assert_eq!(chain.tip().timestamp(), (TIMESTAMP + 12 * BLOCK_INTERVAL) * 1000);
+
+        // destroy
+        let mut template = init();
+        template.push_live_input(&chain, &update_tx.output_pts()[0], None);
+        template.push_empty_witness();
+        chain.test_tx(template.as_json());
+        assert!(chain
+            .get_label("record")
+            .and_then(|out_point| chain.get_live_cell(out_point))
+            .is_none());
+
+        // The spent cell can not be committed again.
+        assert!(chain.commit_tx(&update_tx).is_err());
+    }
+}
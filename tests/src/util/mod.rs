@@ -1,8 +1,22 @@
+pub mod binary_registry;
+pub mod cell_dump;
+pub mod cell_generator;
+pub mod cell_registry;
+pub mod chain_env;
 pub mod constants;
 pub mod error;
+pub mod fee;
+pub mod mock_chain;
+pub mod out_point;
+pub mod signer;
 pub mod since_util;
+pub mod snapshot;
 pub mod template_generator;
 pub mod template_parser;
+pub mod template_schema;
+pub mod tx_builder;
+pub mod tx_lint;
+pub mod type_id;
 
 mod util;
 
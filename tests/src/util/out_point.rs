@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use ckb_types::packed::{Byte32, OutPoint};
+use ckb_types::prelude::*;
+
+/// The namespaces of mock out points, out points in different namespaces never collide.
+///
+/// The tx_hash of a mock out point is the namespace and the index in big-endian, so it is readable when debugging:
+///
+/// ```json
+/// 0x0000000000000000000000000000000000000000000000 03 0000000000000002
+///                                                  ^ namespace         ^ index
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutPointSpace {
+    /// Contracts in cell_deps, the index is the index in cell_deps.
+    ContractDep = 1,
+    /// Full cells in cell_deps, including dep groups, the index is the index in cell_deps.
+    CellDep = 2,
+    /// The members of dep groups, the index is the index of the group in high 32 bits and the member in low 32 bits.
+    DepGroupMember = 3,
+    /// Full cells in inputs, the index is the index in inputs.
+    Input = 4,
+    /// Cells inserted into a MockChain directly, the index is allocated in order.
+    Output = 5,
+}
+
+impl OutPointSpace {
+    pub fn out_point(self, index: u64) -> OutPoint {
+        let mut tx_hash = [0u8; 32];
+        tx_hash[23] = self as u8;
+        tx_hash[24..].copy_from_slice(&index.to_be_bytes());
+
+        OutPoint::new_builder()
+            .tx_hash(Byte32::from_slice(&tx_hash).expect("The tx_hash should be 32 bytes."))
+            .index(0u32.pack())
+            .build()
+    }
+}
+
+/// Allocate mock out points in order within each namespace, so the same sequence of allocations always results in the
+/// same out points, and the hashes of transactions are stable between runs.
+#[derive(Debug, Clone, Default)]
+pub struct OutPointAllocator {
+    next_indexes: HashMap<OutPointSpace, u64>,
+}
+
+impl OutPointAllocator {
+    pub fn new() -> Self {
+        OutPointAllocator::default()
+    }
+
+    pub fn alloc(&mut self, space: OutPointSpace) -> OutPoint {
+        let index = self.next_indexes.entry(space).or_insert(0);
+        let out_point = space.out_point(*index);
+        *index += 1;
+
+        out_point
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_out_point_space() {
+        let out_point = OutPointSpace::Input.out_point(2);
+        assert_eq!(
+            format!("{:x}", out_point.tx_hash()),
+            "0000000000000000000000000000000000000000000000040000000000000002"
+        );
+        assert_ne!(out_point, OutPointSpace::CellDep.out_point(2));
+
+        let mut allocator = OutPointAllocator::new();
+        assert_eq!(
+            allocator.alloc(OutPointSpace::Output),
+            OutPointSpace::Output.out_point(0)
+        );
+        assert_eq!(
+            allocator.alloc(OutPointSpace::Output),
+            OutPointSpace::Output.out_point(1)
+        );
+        assert_eq!(allocator.alloc(OutPointSpace::Input), OutPointSpace::Input.out_point(0));
+    }
+}
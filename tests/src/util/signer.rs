@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+
+use ckb_crypto::secp::Privkey;
+use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_types::core::TransactionView;
+use ckb_types::packed::{Byte32, BytesOpt, Script, WitnessArgs};
+use ckb_types::prelude::*;
+use ckb_types::{bytes, H256};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const SIGNATURE_SIZE: usize = 65;
+
+/// Derive the private key of a named test account, so tests can refer to accounts like "alice" and "bob".
+///
+/// The key is the blake2b hash of the name, so it is the same between runs and never needs to be stored.
+pub fn privkey(name: &str) -> Privkey {
+    Privkey::from_slice(&blake2b_256(format!("test-account:{}", name)))
+}
+
+/// The blake160 hash of the compressed public key, it is the args of secp256k1_blake160_sighash_all.
+pub fn pubkey_hash(name: &str) -> [u8; 20] {
+    let pubkey = privkey(name)
+        .pubkey()
+        .unwrap_or_else(|_| panic!("The private key of {} should be valid.", name));
+    let mut hash = [0u8; 20];
+    hash.copy_from_slice(&blake2b_256(pubkey.serialize())[..20]);
+    hash
+}
+
+/// Calculate the sighash-all message of the lock group.
+///
+/// The lock of the first witness in the group is replaced with the placeholder, then the transaction hash and all
+/// witnesses of the group and the witnesses without inputs are hashed.
+pub fn sighash_all_message(
+    tx_hash: &Byte32,
+    witnesses: &[bytes::Bytes],
+    group: &[usize],
+    inputs_len: usize,
+    placeholder: &[u8],
+) -> Result<H256, Box<dyn StdError>> {
+    let first = group[0];
+    let witness_args = parse_witness_args(first, &witnesses[first])?;
+    let first_witness = witness_args
+        .as_builder()
+        .lock(BytesOpt::new_builder().set(Some(placeholder.pack())).build())
+        .build();
+
+    let mut hasher = new_blake2b();
+    hasher.update(tx_hash.as_slice());
+    let mut hash_witness = |witness: &[u8]| {
+        hasher.update(&(witness.len() as u64).to_le_bytes());
+        hasher.update(witness);
+    };
+    hash_witness(first_witness.as_slice());
+    for i in group[1..].iter() {
+        hash_witness(&witnesses[*i]);
+    }
+    for witness in witnesses.iter().skip(inputs_len) {
+        hash_witness(witness);
+    }
+
+    let mut message = [0u8; 32];
+    hasher.finalize(&mut message);
+    Ok(H256::from(message))
+}
+
+/// Sign the message with the named key, the signature is 65 bytes which contains the recovery ID at the end.
+pub fn sign(name: &str, message: &H256) -> Result<bytes::Bytes, Box<dyn StdError>> {
+    let signature = privkey(name)
+        .sign_recoverable(message)
+        .map_err(|err| format!("Sign with the key of {} failed: {}", name, err))?;
+    Ok(bytes::Bytes::from(signature.serialize()))
+}
+
+/// The config of secp256k1_blake160_multisig_all, it is M-of-N where M is the threshold and N is the count of keys.
+///
+/// In JSON it is like below:
+///
+/// ```json
+/// {
+///     "require_first_n": 0, // could be omit if it is 0, the first N keys must sign in order
+///     "threshold": 2,
+///     "keys": ["alice", "bob", "carol"], // the names of test accounts
+///     "since": null | u64 // the since lock appended to args, inputs must have since no less than it
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigConfig {
+    #[serde(default)]
+    pub require_first_n: u8,
+    pub threshold: u8,
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub since: Option<u64>,
+}
+
+impl MultisigConfig {
+    pub fn new(keys: &[&str], threshold: u8) -> Self {
+        MultisigConfig {
+            require_first_n: 0,
+            threshold,
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            since: None,
+        }
+    }
+
+    pub fn require_first_n(mut self, require_first_n: u8) -> Self {
+        self.require_first_n = require_first_n;
+        self
+    }
+
+    pub fn since(mut self, since: u64) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// The multisig script is `0 | require_first_n | threshold | pubkeys_count | blake160(pubkey) * pubkeys_count`.
+    pub fn multisig_script(&self) -> Vec<u8> {
+        let mut script = vec![0, self.require_first_n, self.threshold, self.keys.len() as u8];
+        for key in self.keys.iter() {
+            script.extend(pubkey_hash(key));
+        }
+        script
+    }
+
+    /// The args is the blake160 hash of the multisig script, and the since in little-endian if there is.
+    pub fn lock_args(&self) -> Vec<u8> {
+        let mut args = blake2b_256(self.multisig_script())[..20].to_vec();
+        if let Some(since) = self.since {
+            args.extend(since.to_le_bytes());
+        }
+        args
+    }
+}
+
+/// How the lock group of an input is signed, it is parsed from the `tmp_signer` field of inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Signer {
+    /// Sign with secp256k1_blake160_sighash_all, it is the name of the test account in JSON.
+    SighashAll(String),
+    /// Sign with secp256k1_blake160_multisig_all, it is like below in JSON:
+    ///
+    /// ```json
+    /// {
+    ///     "multisig": MultisigConfig,
+    ///     "signers": ["alice", "carol"] // the keys to sign with, they are sorted in the order of config
+    /// }
+    /// ```
+    MultisigAll {
+        #[serde(rename = "multisig")]
+        config: MultisigConfig,
+        signers: Vec<String>,
+    },
+}
+
+impl Signer {
+    pub fn from_json(field_name: &str, value: &Value) -> Result<Self, Box<dyn StdError>> {
+        if let Some(name) = value.as_str() {
+            return Ok(Signer::SighashAll(name.to_string()));
+        }
+
+        let config: MultisigConfig = serde_json::from_value(value["multisig"].clone())
+            .map_err(|err| format!("Field `{}.multisig` parse failed: {}", field_name, err))?;
+        let signers: Vec<String> = serde_json::from_value(value["signers"].clone())
+            .map_err(|err| format!("Field `{}.signers` parse failed: {}", field_name, err))?;
+
+        Ok(Signer::MultisigAll { config, signers })
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("The signer should always be serializable.")
+    }
+
+    /// The lock with signatures zeroed, it is used when calculating the message.
+    fn placeholder(&self) -> Vec<u8> {
+        match self {
+            Signer::SighashAll(_) => vec![0u8; SIGNATURE_SIZE],
+            Signer::MultisigAll { config, signers } => {
+                let mut lock = config.multisig_script();
+                lock.extend(vec![0u8; SIGNATURE_SIZE * signers.len()]);
+                lock
+            }
+        }
+    }
+
+    /// The lock with signatures, the multisig signatures are in the order of keys in config, so the first N keys are
+    /// also the first N signatures.
+    fn sign(&self, message: &H256) -> Result<bytes::Bytes, Box<dyn StdError>> {
+        match self {
+            Signer::SighashAll(name) => sign(name, message),
+            Signer::MultisigAll { config, signers } => {
+                let mut signers = signers.clone();
+                signers.sort_by_key(|name| config.keys.iter().position(|key| key == name).unwrap_or(usize::MAX));
+
+                let mut lock = config.multisig_script();
+                for name in signers.iter() {
+                    lock.extend(sign(name, message)?);
+                }
+                Ok(bytes::Bytes::from(lock))
+            }
+        }
+    }
+}
+
+/// Sign every lock group which contains any input in signers, the signed lock is put into the first witness of each
+/// group.
+///
+/// The signers are pairs of input index and signer, the locks are the lock scripts of all inputs.
+pub fn sign_inputs(
+    tx: &TransactionView,
+    locks: &[Script],
+    signers: &[(usize, Signer)],
+) -> Result<Vec<bytes::Bytes>, Box<dyn StdError>> {
+    let mut witnesses: Vec<bytes::Bytes> = tx.witnesses().into_iter().map(|witness| witness.unpack()).collect();
+    let groups = lock_groups(locks);
+
+    let mut signed = vec![];
+    for (i, signer) in signers.iter() {
+        let group = groups
+            .iter()
+            .find(|group| group.contains(i))
+            .ok_or_else(|| format!("Field `inputs[{}].tmp_signer` is invalid: the input does not exist.", i))?;
+        if signed.contains(&group[0]) {
+            continue;
+        }
+        if witnesses.len() <= group[0] {
+            witnesses.resize(group[0] + 1, bytes::Bytes::new());
+        }
+
+        let message = sighash_all_message(&tx.hash(), &witnesses, group, locks.len(), &signer.placeholder())?;
+        let lock = signer.sign(&message)?;
+        witnesses[group[0]] = set_witness_lock(group[0], &witnesses[group[0]], lock)?;
+        signed.push(group[0]);
+    }
+
+    Ok(witnesses)
+}
+
+/// Group the inputs by their lock scripts like the script groups of CKB, the groups are in the order of first inputs.
+pub fn lock_groups(locks: &[Script]) -> Vec<Vec<usize>> {
+    let mut indexes: HashMap<Byte32, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for (i, lock) in locks.iter().enumerate() {
+        match indexes.get(&lock.calc_script_hash()) {
+            Some(index) => groups[*index].push(i),
+            None => {
+                indexes.insert(lock.calc_script_hash(), groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    groups
+}
+
+/// Replace the lock of the witness, an empty witness is treated as a default WitnessArgs.
+pub fn set_witness_lock(index: usize, witness: &[u8], lock: bytes::Bytes) -> Result<bytes::Bytes, Box<dyn StdError>> {
+    let witness_args = parse_witness_args(index, witness)?
+        .as_builder()
+        .lock(BytesOpt::new_builder().set(Some(lock.pack())).build())
+        .build();
+    Ok(witness_args.as_bytes())
+}
+
+fn parse_witness_args(index: usize, witness: &[u8]) -> Result<WitnessArgs, Box<dyn StdError>> {
+    if witness.is_empty() {
+        return Ok(WitnessArgs::default());
+    }
+
+    WitnessArgs::from_slice(witness).map_err(|err| {
+        format!(
+            "Field `witnesses[{}]` should be a WitnessArgs to be signed: {}",
+            index, err
+        )
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_named_keys() {
+        // The keys are stable between runs and different between names.
+        assert_eq!(pubkey_hash("alice"), pubkey_hash("alice"));
+        assert_ne!(pubkey_hash("alice"), pubkey_hash("bob"));
+
+        let message = H256::from([1u8; 32]);
+        let signature = sign("alice", &message).unwrap();
+        assert_eq!(signature.len(), SIGNATURE_SIZE);
+        let signature = ckb_crypto::secp::Signature::from_slice(&signature).unwrap();
+        let pubkey = signature.recover(&message).unwrap();
+        assert_eq!(pubkey, privkey("alice").pubkey().unwrap());
+    }
+
+    #[test]
+    fn test_multisig_config() {
+        let config = MultisigConfig::new(&["alice", "bob", "carol"], 2).require_first_n(1);
+        let script = config.multisig_script();
+        assert_eq!(&script[..4], &[0, 1, 2, 3]);
+        assert_eq!(&script[4..24], &pubkey_hash("alice"));
+        assert_eq!(script.len(), 4 + 20 * 3);
+
+        assert_eq!(config.lock_args().len(), 20);
+        let config = config.since(100);
+        assert_eq!(config.lock_args().len(), 28);
+        assert_eq!(&config.lock_args()[20..], &100u64.to_le_bytes());
+
+        let signer = Signer::MultisigAll {
+            config,
+            signers: vec![String::from("carol"), String::from("alice")],
+        };
+        assert_eq!(Signer::from_json("tmp_signer", &signer.to_json()).unwrap(), signer);
+    }
+}
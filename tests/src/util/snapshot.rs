@@ -0,0 +1,334 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::{env, fs};
+
+use ckb_types::core::Cycle;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use super::util;
+
+const CYCLE_SNAPSHOT: &str = "CYCLE_SNAPSHOT";
+const CYCLE_SNAPSHOT_TOLERANCE: &str = "CYCLE_SNAPSHOT_TOLERANCE";
+const SNAPSHOT_FILE: &str = "cycle_snapshots.json";
+// The tolerance is in percent, so cycles may grow 1% before the snapshot check fails.
+const DEFAULT_TOLERANCE: f64 = 1.0;
+
+lazy_static! {
+    // All tests run in the same process, so this lock is enough to keep the snapshot file consistent.
+    static ref SNAPSHOT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+thread_local! {
+    // Each test runs in its own thread, this counter distinguishes multiple transactions in one test.
+    static TX_COUNTER: Cell<usize> = const { Cell::new(0) };
+}
+
+pub enum SnapshotMode {
+    Off,
+    Verify,
+    Update,
+}
+
+impl FromStr for SnapshotMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(SnapshotMode::Off),
+            "verify" => Ok(SnapshotMode::Verify),
+            "update" => Ok(SnapshotMode::Update),
+            _ => Err("Environment variable CYCLE_SNAPSHOT only support \"off\", \"verify\" and \"update\"."),
+        }
+    }
+}
+
+/// The cost of a transaction which is recorded in the snapshot file.
+///
+/// The `scripts` field is keyed by labels like `playground.type` or `always-success.lock`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CycleSnapshot {
+    pub tx_size: u64,
+    pub cycles: Cycle,
+    pub scripts: BTreeMap<String, Cycle>,
+}
+
+impl CycleSnapshot {
+    pub fn new(tx_size: usize, cycles: Cycle, script_cycles: &[(String, Cycle)]) -> Self {
+        let mut scripts = BTreeMap::new();
+        for (label, cycles) in script_cycles.iter() {
+            let mut key = label.to_owned();
+            let mut n = 1;
+            while scripts.contains_key(&key) {
+                n += 1;
+                key = format!("{}#{}", label, n);
+            }
+            scripts.insert(key, *cycles);
+        }
+
+        CycleSnapshot {
+            tx_size: tx_size as u64,
+            cycles,
+            scripts,
+        }
+    }
+}
+
+fn snapshot_mode() -> SnapshotMode {
+    match env::var(CYCLE_SNAPSHOT) {
+        Ok(val) => val
+            .parse()
+            .expect("Snapshot mode should be one of off, verify and update."),
+        Err(_) => SnapshotMode::Off,
+    }
+}
+
+fn snapshot_tolerance() -> f64 {
+    match env::var(CYCLE_SNAPSHOT_TOLERANCE) {
+        Ok(val) => val
+            .parse()
+            .expect("Environment variable CYCLE_SNAPSHOT_TOLERANCE should be a percentage like 1.5 ."),
+        Err(_) => DEFAULT_TOLERANCE,
+    }
+}
+
+fn snapshot_path() -> PathBuf {
+    let mut file_path = env::current_dir().unwrap();
+    file_path.push(SNAPSHOT_FILE);
+    file_path
+}
+
+/// Generate the key of current transaction, it is the name of the test and a sequence number if the test has
+/// executed more than one transaction.
+fn snapshot_key() -> String {
+    let test_name = util::current_test_name();
+    let n = TX_COUNTER.with(|counter| {
+        let n = counter.get() + 1;
+        counter.set(n);
+        n
+    });
+
+    if n > 1 {
+        format!("{}#{}", test_name, n)
+    } else {
+        test_name
+    }
+}
+
+fn load_snapshots() -> BTreeMap<String, CycleSnapshot> {
+    match fs::read_to_string(snapshot_path()) {
+        Ok(raw_json) => serde_json::from_str(&raw_json).expect("The cycle snapshot file should be valid JSON."),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+fn save_snapshots(snapshots: &BTreeMap<String, CycleSnapshot>) {
+    let file_path = snapshot_path();
+    let data = serde_json::to_string_pretty(snapshots).unwrap();
+    fs::write(&file_path, data).unwrap_or_else(|_| panic!("Expect file path {:?} to be writable.", file_path));
+}
+
+/// Record or verify the cost of the transaction according to the environment variable CYCLE_SNAPSHOT.
+///
+/// - `off`: do nothing, this is the default mode.
+/// - `verify`: compare with the snapshot file and panic if anything grows beyond CYCLE_SNAPSHOT_TOLERANCE percent.
+/// - `update`: overwrite the snapshot of the current test, the snapshots of transactions the test no longer executes
+///   are dropped.
+pub fn check_cycle_snapshot(current: CycleSnapshot) {
+    let mode = snapshot_mode();
+    if let SnapshotMode::Off = mode {
+        return;
+    }
+
+    let key = snapshot_key();
+    let _guard = SNAPSHOT_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let mut snapshots = load_snapshots();
+
+    match mode {
+        SnapshotMode::Update => {
+            update_snapshot(&mut snapshots, key, current);
+            save_snapshots(&snapshots);
+        }
+        SnapshotMode::Verify => {
+            let expected = match snapshots.get(&key) {
+                Some(val) => val,
+                None => {
+                    println!(
+                        "No cycle snapshot of {} found, run with {}=update to record it.",
+                        key, CYCLE_SNAPSHOT
+                    );
+                    return;
+                }
+            };
+
+            let tolerance = snapshot_tolerance();
+            let (table, exceeded) = diff_table(expected, &current, tolerance);
+            println!("{}", table);
+            if exceeded {
+                panic!(
+                    "\n======\nThe cost of {} exceeded the snapshot by more than {}%:\n{}\n======\n",
+                    key, tolerance, table
+                );
+            }
+        }
+        SnapshotMode::Off => unreachable!(),
+    }
+}
+
+/// Split the key into the test name and the sequence number of the transaction.
+fn split_key(key: &str) -> (&str, usize) {
+    match key.rsplit_once('#') {
+        Some((test_name, n)) => match n.parse() {
+            Ok(n) => (test_name, n),
+            Err(_) => (key, 1),
+        },
+        None => (key, 1),
+    }
+}
+
+/// Insert the snapshot, and drop the snapshots of the same test with greater sequence numbers.
+///
+/// Transactions of a test are checked in order, so when the last one is updated only the transactions which the test
+/// still executes are left.
+fn update_snapshot(snapshots: &mut BTreeMap<String, CycleSnapshot>, key: String, current: CycleSnapshot) {
+    let (test_name, n) = split_key(&key);
+    let test_name = test_name.to_owned();
+    snapshots.retain(|item, _| {
+        let (item_test_name, item_n) = split_key(item);
+        item_test_name != test_name || item_n <= n
+    });
+    snapshots.insert(key, current);
+}
+
+/// Render the difference as a table, and return whether anything grows beyond the tolerance.
+///
+/// New and removed scripts are listed in the table but never fail the check, the snapshot is expected to be updated
+/// when scripts are added or removed.
+fn diff_table(expected: &CycleSnapshot, current: &CycleSnapshot, tolerance: f64) -> (String, bool) {
+    let mut rows = vec![("tx_size".to_string(), Some(expected.tx_size), Some(current.tx_size))];
+    rows.push(("cycles".to_string(), Some(expected.cycles), Some(current.cycles)));
+
+    let mut labels = expected
+        .scripts
+        .keys()
+        .chain(current.scripts.keys())
+        .collect::<Vec<_>>();
+    labels.sort();
+    labels.dedup();
+    for label in labels {
+        rows.push((
+            label.to_owned(),
+            expected.scripts.get(label).copied(),
+            current.scripts.get(label).copied(),
+        ));
+    }
+
+    let mut exceeded = false;
+    let mut table = format!(
+        "{:<40} {:>14} {:>14} {:>14} {:>9}\n",
+        "", "snapshot", "current", "diff", "change"
+    );
+    for (label, expected, current) in rows {
+        let fmt_val = |val: Option<u64>| val.map(|v| v.to_string()).unwrap_or_else(|| String::from("-"));
+        let (diff, change): (String, Option<f64>) = match (expected, current) {
+            (Some(expected), Some(current)) => {
+                let diff = current as i128 - expected as i128;
+                let change = if expected == 0 {
+                    if current == 0 {
+                        0.0
+                    } else {
+                        f64::INFINITY
+                    }
+                } else {
+                    diff as f64 * 100.0 / expected as f64
+                };
+                (format!("{:+}", diff), Some(change))
+            }
+            (None, Some(_)) => (String::from("new"), None),
+            _ => (String::from("removed"), None),
+        };
+        let flag = match change {
+            Some(change) if change > tolerance => {
+                exceeded = true;
+                " ⬆"
+            }
+            _ => "",
+        };
+        let change = change
+            .map(|change| format!("{:.2}%", change))
+            .unwrap_or_else(|| String::from("-"));
+
+        table += &format!(
+            "{:<40} {:>14} {:>14} {:>14} {:>9}{}\n",
+            label,
+            fmt_val(expected),
+            fmt_val(current),
+            diff,
+            change,
+            flag
+        );
+    }
+
+    (table, exceeded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(cycles: Cycle, scripts: &[(&str, Cycle)]) -> CycleSnapshot {
+        let scripts = scripts
+            .iter()
+            .map(|(label, cycles)| (label.to_string(), *cycles))
+            .collect::<Vec<_>>();
+        CycleSnapshot::new(100, cycles, &scripts)
+    }
+
+    #[test]
+    fn test_diff_tolerance() {
+        let expected = snapshot(1000, &[("playground.type", 1000)]);
+
+        // Growing exactly by the tolerance is still acceptable.
+        let (_, exceeded) = diff_table(&expected, &snapshot(1010, &[("playground.type", 1010)]), 1.0);
+        assert!(!exceeded);
+        let (table, exceeded) = diff_table(&expected, &snapshot(1011, &[("playground.type", 1011)]), 1.0);
+        assert!(exceeded);
+        assert!(table.contains("1.10%"), "{}", table);
+
+        // Decreasing never fails.
+        let (_, exceeded) = diff_table(&expected, &snapshot(500, &[("playground.type", 500)]), 0.0);
+        assert!(!exceeded);
+    }
+
+    #[test]
+    fn test_diff_new_and_removed_scripts() {
+        let expected = snapshot(1000, &[("playground.type", 1000)]);
+        let current = snapshot(1000, &[("playground.lock", 10)]);
+
+        let (table, exceeded) = diff_table(&expected, &current, 1.0);
+        assert!(!exceeded, "{}", table);
+        let row = |label: &str| table.lines().find(|line| line.starts_with(label)).unwrap().to_owned();
+        assert!(row("playground.lock").contains("new"), "{}", table);
+        assert!(row("playground.type").contains("removed"), "{}", table);
+    }
+
+    #[test]
+    fn test_update_snapshot() {
+        let mut snapshots = BTreeMap::new();
+        for key in ["a::test", "a::test#2", "a::test#3", "a::test_other#2", "b::test"] {
+            snapshots.insert(key.to_string(), CycleSnapshot::default());
+        }
+
+        // The test executes two transactions now, so the third one is dropped.
+        update_snapshot(&mut snapshots, String::from("a::test"), snapshot(1, &[]));
+        update_snapshot(&mut snapshots, String::from("a::test#2"), snapshot(2, &[]));
+        assert_eq!(
+            snapshots.keys().map(|key| key.as_str()).collect::<Vec<_>>(),
+            vec!["a::test", "a::test#2", "a::test_other#2", "b::test"]
+        );
+        assert_eq!(snapshots["a::test#2"].cycles, 2);
+    }
+}
@@ -4,14 +4,20 @@ use std::io::Write;
 use std::{env, str};
 
 use ckb_types::bytes;
-use ckb_types::packed::{Byte, Byte32, Bytes, Script};
-use ckb_types::prelude::{Builder, Entity};
+use ckb_types::packed::{Byte, Byte32, Bytes, OutPoint, Script};
+use ckb_types::prelude::{Builder, Entity, Pack, Unpack};
+use common::entity;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
+use super::cell_generator::get_cell_generator;
+use super::cell_registry::{CellRegistry, LiveCell};
 use super::constants::*;
+use super::mock_chain::MockChain;
+use super::signer::{MultisigConfig, Signer};
 use super::since_util::SinceFlag;
-use super::{since_util, util};
+use super::template_parser::TemplateParser;
+use super::{fee, signer, since_util, util};
 
 pub enum ContractType {
     DeployedContract,
@@ -20,6 +26,12 @@ pub enum ContractType {
     SharedLib,
 }
 
+/// How the callee is called by the caller, it decides the minimum VM version of the transaction.
+pub enum CallType {
+    Exec,
+    Spawn,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum SubAccountActionType {
@@ -56,6 +68,24 @@ pub fn gen_fake_signhash_all_lock(lock_args: &str) -> Script {
         .build()
 }
 
+/// The lock of the named test account with the real secp256k1_blake160_sighash_all, sign the inputs with it by
+/// TemplateGenerator::sign_input.
+pub fn gen_sighash_all_lock(key_name: &str) -> Value {
+    json!({
+        "code_hash": "{{secp256k1_blake160_sighash_all}}",
+        "args": util::bytes_to_hex(&signer::pubkey_hash(key_name))
+    })
+}
+
+/// The lock of the multisig config with the real secp256k1_blake160_multisig_all, sign the inputs with it by
+/// TemplateGenerator::sign_input_multisig.
+pub fn gen_multisig_lock(config: &MultisigConfig) -> Value {
+    json!({
+        "code_hash": "{{secp256k1_blake160_multisig_all}}",
+        "args": util::bytes_to_hex(&config.lock_args())
+    })
+}
+
 pub fn gen_since(relative_flag: SinceFlag, metric_flag: SinceFlag, value: u64) -> Option<u64> {
     let mut since = 0u64;
     since = since_util::set_relative_flag(since, relative_flag);
@@ -102,8 +132,8 @@ fn parse_json_array<'a>(field_name: &str, field: &'a Value) -> &'a [Value] {
 /// ```json
 /// // input
 /// {
-///     code_hash: "{{xxx-cell-type}}"
-///     hash_type: "type", // could be omit if it is "type"
+///     code_hash: "{{xxx-cell-type}}" // or "{{data_hash:xxx}}" for contracts referenced by data hash
+///     hash_type: "type", // could be omit if it is "type", the others are "data", "data1" and "data2"
 ///     args: "" // could be omit if it it empty
 /// }
 /// // output
@@ -117,10 +147,7 @@ fn parse_json_script(field_name: &str, field: &Value) -> Value {
     let code_hash = field["code_hash"]
         .as_str()
         .expect(&format!("{} is missing", field_name));
-    let hash_type = match field["hash_type"].as_str() {
-        Some("data") => "data",
-        _ => "type",
-    };
+    let hash_type = parse_json_hash_type(field_name, field);
     let args = match field["args"].as_str() {
         Some(val) => val,
         _ => "",
@@ -133,6 +160,110 @@ fn parse_json_script(field_name: &str, field: &Value) -> Value {
     })
 }
 
+/// Calculate the minimal capacity of a cell in shannons, it is 1 CKB per byte of the cell.
+///
+/// The variables like `{{xxx}}` in args are counted as 32 bytes, because they are replaced with hashes by the parser.
+fn occupied_capacity(lock: &Value, type_: &Value, data_len: usize) -> u64 {
+    // The capacity field itself occupies 8 bytes.
+    let size = 8 + script_size(lock) + script_size(type_) + data_len;
+    size as u64 * ONE_CKB
+}
+
+/// Resolve the capacity of a cell in the template, "auto" means the occupied capacity like in push_cell.
+fn cell_capacity(field_name: &str, cell: &Value) -> u64 {
+    if cell["capacity"].as_str() == Some("auto") {
+        let data_len = cell["tmp_data"].as_str().unwrap_or("0x").trim_start_matches("0x").len() / 2;
+        occupied_capacity(&cell["lock"], &cell["type"], data_len)
+    } else {
+        util::parse_json_capacity(&format!("{}.capacity", field_name), &cell["capacity"], None)
+    }
+}
+
+/// Find the live cell of the input which references a cell in the registry.
+fn registered_cell<'a>(field_name: &str, cell_ref: &Value, registry: &'a CellRegistry) -> &'a LiveCell {
+    let out_point = match cell_ref["tmp_ref"].as_str() {
+        Some(label) => registry
+            .get_label(label)
+            .cloned()
+            .unwrap_or_else(|| panic!("{}.tmp_ref is invalid: label {} is not registered", field_name, label)),
+        None => {
+            let tx_hash = util::parse_json_hex(
+                &format!("{}.out_point.tx_hash", field_name),
+                &cell_ref["out_point"]["tx_hash"],
+            );
+            let index = util::parse_json_u32(
+                &format!("{}.out_point.index", field_name),
+                &cell_ref["out_point"]["index"],
+                None,
+            );
+            OutPoint::new_builder()
+                .tx_hash(Byte32::from_slice(&tx_hash).expect("The tx_hash should be 32 bytes."))
+                .index(index.pack())
+                .build()
+        }
+    };
+
+    registry.get_cell(&out_point).unwrap_or_else(|| {
+        panic!(
+            "{} is invalid: cell {} is not a live cell in the registry",
+            field_name, out_point
+        )
+    })
+}
+
+fn input_capacity(field_name: &str, cell: &Value, registry: &CellRegistry) -> u64 {
+    if cell["tmp_type"].is_null() {
+        registered_cell(field_name, cell, registry).output.capacity().unpack()
+    } else {
+        cell_capacity(field_name, cell)
+    }
+}
+
+fn input_lock(field_name: &str, cell: &Value, registry: &CellRegistry) -> Value {
+    if !cell["tmp_type"].is_null() {
+        return cell["lock"].clone();
+    }
+
+    let lock = registered_cell(field_name, cell, registry).output.lock();
+    let hash_type = match u8::from(lock.hash_type()) {
+        0 => "data",
+        1 => "type",
+        2 => "data1",
+        _ => "data2",
+    };
+    json!({
+        "code_hash": format!("0x{:x}", lock.code_hash()),
+        "hash_type": hash_type,
+        "args": util::bytes_to_hex(&lock.args().raw_data())
+    })
+}
+
+fn script_size(script: &Value) -> usize {
+    if script.is_null() {
+        return 0;
+    }
+
+    let args = script["args"].as_str().unwrap_or("").trim_start_matches("0x");
+    let hash_hex = "00".repeat(32);
+    let args = RE_DATA_HASH_VARIABLE.replace_all(args, hash_hex.as_str());
+    let args = RE_VARIABLE.replace_all(&args, hash_hex.as_str());
+
+    // code_hash + hash_type + args
+    32 + 1 + args.len() / 2
+}
+
+/// Parse the hash_type of Script, it is "type" if omitted and unknown values are rejected.
+fn parse_json_hash_type<'a>(field_name: &str, field: &'a Value) -> &'a str {
+    match field["hash_type"].as_str() {
+        None => "type",
+        Some(val @ ("type" | "data" | "data1" | "data2")) => val,
+        Some(val) => panic!(
+            "{}.hash_type only support \"type\", \"data\", \"data1\" and \"data2\", but got \"{}\".",
+            field_name, val
+        ),
+    }
+}
+
 /// Parse struct Script to hex of molecule encoding, if field is null will return Script::default()
 ///
 /// Example:
@@ -151,6 +282,12 @@ fn parse_json_script_to_mol(field_name: &str, field: &Value) -> Script {
     let code_hash = field["code_hash"]
         .as_str()
         .expect(&format!("{} is missing", field_name));
+    if RE_DATA_HASH_VARIABLE.is_match(code_hash) {
+        panic!(
+            "{} is referenced by data hash, it can only be resolved by TemplateParser with the binary in cell_deps.",
+            field_name
+        );
+    }
     let code_hash_bytes = if let Some(caps) = RE_VARIABLE.captures(code_hash) {
         let cap = caps.get(1).expect("The captures[1] should always exist.");
         util::get_type_id_bytes(cap.as_str())
@@ -158,8 +295,10 @@ fn parse_json_script_to_mol(field_name: &str, field: &Value) -> Script {
         util::hex_to_bytes(code_hash)
     };
 
-    let hash_type = match field["hash_type"].as_str() {
-        Some("data") => ScriptHashType::Data,
+    let hash_type = match parse_json_hash_type(field_name, field) {
+        "data" => ScriptHashType::Data,
+        "data1" => ScriptHashType::Data1,
+        "data2" => ScriptHashType::Data2,
         _ => ScriptHashType::Type,
     };
     let args = match field["args"].as_str() {
@@ -174,12 +313,43 @@ fn parse_json_script_to_mol(field_name: &str, field: &Value) -> Script {
         .build()
 }
 
+fn contract_cell_json(contract_filename: &str, type_: ContractType) -> Value {
+    match type_ {
+        ContractType::Contract => {
+            json!({
+                "tmp_type": "contract",
+                "tmp_file_name": contract_filename
+            })
+        }
+        ContractType::DeployedContract => {
+            json!({
+                "tmp_type": "deployed_contract",
+                "tmp_file_name": contract_filename
+            })
+        }
+        ContractType::SharedLib => {
+            json!({
+                "tmp_type": "shared_lib",
+                "tmp_file_name": contract_filename
+            })
+        }
+        ContractType::DeployedSharedLib => {
+            json!({
+                "tmp_type": "deployed_shared_lib",
+                "tmp_file_name": contract_filename
+            })
+        }
+    }
+}
+
 fn length_of(data: &[u8]) -> Vec<u8> {
     (data.len() as u32).to_le_bytes().to_vec()
 }
 
 pub struct TemplateGenerator {
     loaded_contracts: Vec<String>,
+    // The minimum VM version required by the syscalls of contracts, it is output as tmp_env.vm_version.
+    vm_version: Option<u8>,
     // Transaction fields
     pub header_deps: Vec<Value>,
     pub cell_deps: Vec<Value>,
@@ -193,6 +363,7 @@ impl TemplateGenerator {
     pub fn new(action: &str, params_opt: Option<Bytes>) -> TemplateGenerator {
         TemplateGenerator {
             loaded_contracts: vec![],
+            vm_version: None,
             header_deps: Vec::new(),
             cell_deps: Vec::new(),
             inputs: Vec::new(),
@@ -212,9 +383,18 @@ impl TemplateGenerator {
     /// {
     ///     "version": u32,
     ///     "number": u64,
-    ///     "timestamp": u64 | "YYYY-MM-DD HH:MM:SS",
+    ///     "timestamp": u64 | "YYYY-MM-DD HH:MM:SS" | "+30d", // numbers are kept as is, literals are converted to
+    ///                                                        // milliseconds by TemplateParser like raw templates
     ///     "epoch": u64,
     ///     "transactions_root": "0x...",
+    ///     // The following fields are optional, they are kept as is.
+    ///     "compact_target": u32,
+    ///     "nonce": u128 | "0x...",
+    ///     "parent_hash": "0x...",
+    ///     "proposals_hash": "0x...",
+    ///     "extra_hash": "0x...",
+    ///     "dao": "0x...",
+    ///     "extension": "0x...",
     /// }
     /// ```
     pub fn push_header_deps(&mut self, header: Value) {
@@ -224,53 +404,92 @@ impl TemplateGenerator {
         } else {
             util::parse_json_u64("header.number", &header["number"], Some(0))
         };
-        let timestamp = util::parse_json_u64("header.timestamp", &header["timestamp"], Some(0));
+        let timestamp = match &header["timestamp"] {
+            // Only check the literal here, so the same literal always becomes the same header.
+            Value::String(_) => {
+                util::parse_json_timestamp_ms("header.timestamp", &header["timestamp"], None);
+                header["timestamp"].clone()
+            }
+            val => json!(util::parse_json_u64("header.timestamp", val, Some(0))),
+        };
         let epoch = util::parse_json_u64("header.epoch", &header["epoch"], Some(0));
         let transactions_root = header["transactions_root"].clone();
 
-        let value = json!({
+        let mut value = json!({
             "version": version,
             "number": number,
             "timestamp": timestamp,
             "epoch": epoch,
             "transactions_root": transactions_root
         });
+        for key in [
+            "compact_target",
+            "nonce",
+            "parent_hash",
+            "proposals_hash",
+            "extra_hash",
+            "dao",
+            "extension",
+        ] {
+            if !header[key].is_null() {
+                value[key] = header[key].clone();
+            }
+        }
 
         self.header_deps.push(value);
     }
 
     pub fn push_contract_cell(&mut self, contract_filename: &str, type_: ContractType) {
-        let value = match type_ {
-            ContractType::Contract => {
-                json!({
-                    "tmp_type": "contract",
-                    "tmp_file_name": contract_filename
-                })
-            }
-            ContractType::DeployedContract => {
-                json!({
-                    "tmp_type": "deployed_contract",
-                    "tmp_file_name": contract_filename
-                })
-            }
-            ContractType::SharedLib => {
-                json!({
-                    "tmp_type": "shared_lib",
-                    "tmp_file_name": contract_filename
-                })
-            }
-            ContractType::DeployedSharedLib => {
-                json!({
-                    "tmp_type": "deployed_shared_lib",
-                    "tmp_file_name": contract_filename
-                })
-            }
-        };
+        let value = contract_cell_json(contract_filename, type_);
 
         self.loaded_contracts.push(contract_filename.to_string());
         self.cell_deps.push(value)
     }
 
+    /// Push a cell dep with dep_type dep_group, its members are mocked as cells but only the group is in cell_deps.
+    ///
+    /// For example, the system script secp256k1_blake160_sighash_all is usually referenced like below:
+    ///
+    /// ```json
+    /// template.push_dep_group(vec![
+    ///     ("secp256k1_blake160_sighash_all", ContractType::DeployedContract),
+    ///     ("secp256k1_data", ContractType::DeployedSharedLib),
+    /// ]);
+    /// ```
+    pub fn push_dep_group(&mut self, members: Vec<(&str, ContractType)>) -> usize {
+        let mut values = vec![];
+        for (contract_filename, type_) in members {
+            values.push(contract_cell_json(contract_filename, type_));
+            self.loaded_contracts.push(contract_filename.to_string());
+        }
+
+        self.cell_deps.push(json!({
+            "tmp_type": "dep_group",
+            "tmp_members": values
+        }));
+        self.cell_deps.len() - 1
+    }
+
+    /// Push a contract which is called by other contracts through exec or spawn.
+    ///
+    /// The callee is usually located by its data hash, so it can be referenced like `{{data_hash:<name>}}` in args.
+    /// The VM version of the transaction is raised to the one the syscall requires. The contract is only pushed if it
+    /// is not loaded yet, so a contract can be both a script of the transaction and a callee.
+    pub fn push_callee(&mut self, contract_filename: &str, type_: ContractType, call_type: CallType) {
+        if !self.loaded_contracts.iter().any(|item| item == contract_filename) {
+            self.push_contract_cell(contract_filename, type_);
+        }
+        self.set_vm_version(match call_type {
+            CallType::Exec => 1,
+            CallType::Spawn => 2,
+        });
+    }
+
+    /// Require the transaction to be verified with at least the VM version.
+    pub fn set_vm_version(&mut self, vm_version: u8) {
+        self.vm_version = Some(self.vm_version.map_or(vm_version, |current| current.max(vm_version)));
+    }
+
     pub fn push_dep(&mut self, cell: Value, version_opt: Option<u32>) -> usize {
         self.push_cell(cell, Source::CellDep, version_opt, None)
     }
@@ -283,6 +502,57 @@ impl TemplateGenerator {
         self.push_cell(cell, Source::Output, version_opt, None)
     }
 
+    /// Push an input which spends a live cell of the MockChain by its real out point.
+    pub fn push_live_input(&mut self, chain: &MockChain, out_point: &OutPoint, since_opt: Option<u64>) -> usize {
+        self.inputs.push(chain.live_input(out_point, since_opt));
+        self.inputs.len() - 1
+    }
+
+    /// Push an input which spends the cell labeled in the CellRegistry, the label comes from `tmp_label` of outputs.
+    pub fn push_ref_input(&mut self, label: &str, since_opt: Option<u64>) -> usize {
+        self.inputs.push(json!({
+            "previous_output": { "tmp_ref": label },
+            "since": since_opt.unwrap_or(0)
+        }));
+        self.inputs.len() - 1
+    }
+
+    /// Sign the lock group of the input with the named test account after the transaction is assembled, the signature
+    /// is put into the lock of the first witness of the group.
+    pub fn sign_input(&mut self, index: usize, key_name: &str) {
+        let input = self
+            .inputs
+            .get_mut(index)
+            .unwrap_or_else(|| panic!("inputs[{}] is missing", index));
+        input["tmp_signer"] = json!(key_name);
+    }
+
+    /// Sign the lock group of the input with the multisig config, the signatures of key_names are put into the lock of
+    /// the first witness of the group in the order of keys in the config.
+    ///
+    /// The key_names could be any test accounts, so there could be less than threshold or unknown keys for negative
+    /// tests.
+    pub fn sign_input_multisig(&mut self, index: usize, config: &MultisigConfig, key_names: &[&str]) {
+        let signer = Signer::MultisigAll {
+            config: config.to_owned(),
+            signers: key_names.iter().map(|name| name.to_string()).collect(),
+        };
+        let input = self
+            .inputs
+            .get_mut(index)
+            .unwrap_or_else(|| panic!("inputs[{}] is missing", index));
+        input["tmp_signer"] = signer.to_json();
+    }
+
+    /// Push a cell dep which references the cell labeled in the CellRegistry.
+    pub fn push_ref_dep(&mut self, label: &str) -> usize {
+        self.cell_deps.push(json!({ "tmp_ref": label }));
+        self.cell_deps.len() - 1
+    }
+
+    /// Push a cell described in the format of the contract of its type script, the description is converted by the
+    /// generator registered for the contract, see cell_generator::CellGenerator. Cells without type script are custom
+    /// cells.
     pub fn push_cell(
         &mut self,
         cell: Value,
@@ -290,33 +560,18 @@ impl TemplateGenerator {
         version_opt: Option<u32>,
         since_opt: Option<u64>,
     ) -> usize {
-        macro_rules! push_cell {
-            ($gen_fn:ident, $cell:expr) => {{
-                let cell = self.$gen_fn($cell);
-                self.push_cell_json(cell, source, since_opt)
-            }};
-            ($data_type:expr, $gen_fn:ident, $version_opt:expr, $cell:expr) => {{
-                let version = if let Some(version) = $version_opt {
-                    version
-                } else {
-                    1
-                };
-
-                let (cell, entity_opt) = self.$gen_fn(version, $cell);
-                self.push_cell_json_with_entity(cell, source, $data_type, version, entity_opt, since_opt)
-            }};
-        }
-
-        if let Some(type_script) = cell.get("type") {
-            let code_hash = type_script
-                .get("code_hash")
-                .expect("cell.type.code_hash is missing")
-                .as_str()
-                .expect("cell.type.code_hash should be a string");
-
-            if let Some(caps) = RE_VARIABLE.captures(code_hash) {
-                let type_id = caps
-                    .get(1)
+        let cell = match cell.get("type").filter(|type_script| !type_script.is_null()) {
+            Some(type_script) => {
+                let code_hash = type_script
+                    .get("code_hash")
+                    .expect("cell.type.code_hash is missing")
+                    .as_str()
+                    .expect("cell.type.code_hash should be a string");
+
+                let type_id = RE_DATA_HASH_VARIABLE
+                    .captures(code_hash)
+                    .or_else(|| RE_VARIABLE.captures(code_hash))
+                    .and_then(|caps| caps.get(1))
                     .map(|m| m.as_str())
                     .expect("type.code_hash is something like '{{...}}'");
 
@@ -324,23 +579,25 @@ impl TemplateGenerator {
                     panic!("The contract {} has no cell_deps, please use TemplateGenerater::push_contract_cell to push the related cell_deps.", type_id);
                 }
 
-                let index = match type_id {
-                    "playground" => push_cell!(gen_custom_cell, cell),
-                    _ => panic!("Unknown type ID {}", type_id),
-                };
-
-                index
-            } else {
-                panic!("{}", "type.code_hash is something like '{{...}}'")
+                let generator = get_cell_generator(type_id).unwrap_or_else(|| {
+                    panic!(
+                        "Can not find the type ID of {}, please check the name of the contract or register a generator with cell_generator::register_cell_generator.",
+                        type_id
+                    )
+                });
+                generator.gen_cell(version_opt.unwrap_or(1), cell)
             }
-        } else {
-            push_cell!(gen_custom_cell, cell)
-        }
+            None => cell,
+        };
+
+        let cell = self.gen_custom_cell(version_opt.unwrap_or(1), cell);
+        self.push_cell_json(cell, source, since_opt)
     }
 
     pub fn push_cell_json(&mut self, mut cell: Value, source: Source, since_opt: Option<u64>) -> usize {
         if !cell["tmp_header"].is_null() {
-            let mut timestamp = util::parse_json_u64("header.timestamp", &cell["tmp_header"]["timestamp"], Some(0));
+            let mut timestamp =
+                util::parse_json_timestamp("cell.header.timestamp", &cell["tmp_header"]["timestamp"], Some(0));
             timestamp = timestamp * 1000; // The timestamp in real block header contains milliseconds.
 
             let field = &mut cell["tmp_header"]["timestamp"];
@@ -376,45 +633,142 @@ impl TemplateGenerator {
     ///
     /// ```json
     /// json!({
-    ///     "capacity": u64,
+    ///     "capacity": u64 | "142 CKB" | "1.5 CKB" | "auto", // auto means the minimal occupied capacity
     ///     "lock": Script,
     ///     "type": null | Script,
-    ///     "data": null | "0x..."
+    ///     "data": null | "0x...",
+    ///     "entity": null | "0x...", // the molecule encoded entity, it is wrapped with the version as the data
+    ///     "label": null | "..." // the label of outputs in the CellRegistry after it is committed to a MockChain
     /// })
     /// ```
-    fn gen_custom_cell(&mut self, cell: Value) -> Value {
-        let capacity: u64 = util::parse_json_u64("cell.capacity", &cell["capacity"], Some(0));
-
+    ///
+    /// The `entity` field conflicts with `data`, see common::entity for the layout of versioned entities.
+    fn gen_custom_cell(&mut self, version: u32, cell: Value) -> Value {
         let lock_script = parse_json_script("cell.lock", &cell["lock"]);
         let type_script = cell["type"].clone();
-        let outputs_data = if !cell["data"].is_null() {
+        let outputs_data = if !cell["entity"].is_null() {
+            assert!(
+                cell["data"].is_null(),
+                "cell.data and cell.entity can not be used together"
+            );
+            let payload = util::parse_json_hex("cell.entity", &cell["entity"]);
+            util::bytes_to_hex(&entity::encode(version, &payload))
+        } else if !cell["data"].is_null() {
             util::bytes_to_hex(&util::parse_json_hex("cell.data", &cell["data"]))
         } else {
             String::from("0x")
         };
 
+        let capacity: u64 = if cell["capacity"].as_str() == Some("auto") {
+            occupied_capacity(&lock_script, &type_script, (outputs_data.len() - 2) / 2)
+        } else {
+            util::parse_json_capacity("cell.capacity", &cell["capacity"], Some(0))
+        };
+
         json!({
             "tmp_header": cell["header"],
             "tmp_type": "full",
             "capacity": capacity,
             "lock": lock_script,
             "type": type_script,
-            "tmp_data": outputs_data
+            "tmp_data": outputs_data,
+            "tmp_label": cell["label"]
         })
     }
 
+    /// Balance the capacity of the transaction, the input at fee_input pays the fee and receives the change.
+    ///
+    /// The change is pushed as an output with the lock of the input, if the change is less than the occupied capacity
+    /// of that output it is paid as fee too. The fee rate is in shannons/KW like the tx-pool of CKB node, and the fee
+    /// actually paid is returned.
+    ///
+    /// Inputs referencing cells in the CellRegistry are not supported, use balance_with_registry for them. Call it after
+    /// all inputs and outputs are pushed.
+    pub fn balance(&mut self, fee_input: usize, fee_rate: u64) -> u64 {
+        self.balance_with_registry(fee_input, fee_rate, &CellRegistry::default())
+    }
+
+    /// Like balance, but inputs like `{ "tmp_ref": "..." }` or `{ "out_point": {...} }` are looked up in the registry,
+    /// e.g. the one of a MockChain.
+    pub fn balance_with_registry(&mut self, fee_input: usize, fee_rate: u64, registry: &CellRegistry) -> u64 {
+        let change_lock = self
+            .inputs
+            .get(fee_input)
+            .map(|input| input_lock(&format!("inputs[{}]", fee_input), &input["previous_output"], registry))
+            .unwrap_or_else(|| panic!("The inputs[{}] for paying fee is not found.", fee_input));
+        let input_capacity = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| input_capacity(&format!("inputs[{}]", i), &input["previous_output"], registry))
+            .sum::<u64>();
+        // The capacities of outputs are resolved in place, so literals like "auto" are also valid for the parser.
+        let mut output_capacity = 0u64;
+        for (i, output) in self.outputs.iter_mut().enumerate() {
+            let capacity = cell_capacity(&format!("outputs[{}]", i), output);
+            output["capacity"] = json!(capacity);
+            output_capacity += capacity;
+        }
+        let available = input_capacity.checked_sub(output_capacity).unwrap_or_else(|| {
+            panic!(
+                "The capacity of outputs {} is more than inputs {}, it can not be balanced.",
+                output_capacity, input_capacity
+            )
+        });
+
+        // The change output does not change the size of transaction whatever its capacity is, so push it first.
+        let change_occupied = occupied_capacity(&change_lock, &Value::Null, 0);
+        let change_index = self.push_output(json!({ "capacity": change_occupied, "lock": change_lock }), None);
+        let fee = self.calc_fee(fee_rate, registry);
+        let fee_paid = if available >= change_occupied + fee {
+            self.outputs[change_index]["capacity"] = json!(available - fee);
+            fee
+        } else {
+            self.outputs.remove(change_index);
+            let fee = self.calc_fee(fee_rate, registry);
+            if available < fee {
+                panic!(
+                    "The capacity of inputs is not enough to pay the fee, {} shannons more are required.",
+                    fee - available
+                );
+            }
+            available
+        };
+
+        println!(
+            "Balanced the transaction with fee {} shannons, the fee rate is {} shannons/KW.",
+            fee_paid, fee_rate
+        );
+        fee_paid
+    }
+
+    fn calc_fee(&self, fee_rate: u64, registry: &CellRegistry) -> u64 {
+        let tx = TemplateParser::from_data(self.as_json(), MAX_CYCLES)
+            .with_registry(registry.clone())
+            .build_tx()
+            .unwrap_or_else(|err| panic!("The transaction can not be built for calculating fee: {}", err));
+
+        // The cycles are unknown before execution, so the fee only covers the size.
+        fee::min_fee(tx.data().serialized_size_in_block(), 0, fee_rate)
+    }
+
     // ======
 
     pub fn as_json(&self) -> Value {
         let mut witnesses = [self.inner_witnesses.clone(), self.outer_witnesses.clone()].concat();
 
-        json!({
+        let mut tx = json!({
             "header_deps": self.header_deps,
             "cell_deps": self.cell_deps,
             "inputs": self.inputs,
             "outputs": self.outputs,
             "witnesses": witnesses,
-        })
+        });
+        if let Some(vm_version) = self.vm_version {
+            tx["tmp_env"] = json!({ "vm_version": vm_version });
+        }
+
+        tx
     }
 
     pub fn write_template(&self, filename: &str) {
@@ -433,3 +787,218 @@ impl TemplateGenerator {
         file.write(data.as_bytes()).expect("Write file failed.");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ckb_types::packed::CellOutput;
+    use common::entity::VersionedEntity;
+
+    use super::*;
+    use crate::util::cell_generator::{register_cell_generator, CellGenerator};
+    use crate::util::template_parser::{test_tx_with_fee, TemplateParser};
+    use crate::util::tx_builder::TxTemplate;
+
+    fn init() -> TemplateGenerator {
+        let mut template = TemplateGenerator::new("balance", None);
+        template.push_contract_cell("always_success", ContractType::DeployedContract);
+        template.push_input(
+            json!({
+                "capacity": "1000 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }),
+            None,
+            None,
+        );
+        template.push_empty_witness();
+        template
+    }
+
+    #[test]
+    fn test_auto_capacity() {
+        let mut template = init();
+        template.push_output(
+            json!({
+                "capacity": "auto",
+                "lock": { "code_hash": "{{always_success}}", "args": "0x{{always_success}}00" },
+                "data": "0x0102"
+            }),
+            None,
+        );
+
+        // capacity(8) + code_hash(32) + hash_type(1) + args(33) + data(2)
+        assert_eq!(template.outputs[0]["capacity"], json!(76 * ONE_CKB));
+    }
+
+    #[test]
+    fn test_balance() {
+        let mut template = init();
+        template.push_output(
+            json!({
+                "capacity": "100 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }),
+            None,
+        );
+
+        let fee = template.balance(0, 1000);
+        assert_eq!(template.outputs.len(), 2);
+        assert_eq!(template.outputs[1]["capacity"], json!(900 * ONE_CKB - fee));
+        let tx = test_tx_with_fee(template.as_json());
+        assert_eq!(fee, tx.data().serialized_size_in_block() as u64);
+
+        // The change is too small for a new cell, so it is paid as fee.
+        let mut template = init();
+        template.push_output(
+            json!({
+                "capacity": "990 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }),
+            None,
+        );
+
+        let fee = template.balance(0, 1000);
+        assert_eq!(template.outputs.len(), 1);
+        assert_eq!(fee, 10 * ONE_CKB);
+    }
+
+    #[test]
+    fn test_balance_mixed_capacities() {
+        let mut template = init();
+        let lock = json!({ "code_hash": "{{always_success}}", "hash_type": "type", "args": "0x" });
+        // Cells pushed as JSON keep the capacity literals, they are resolved when balancing.
+        for capacity in [json!("auto"), json!("142 CKB"), json!(format!("0x{:x}", 100 * ONE_CKB))] {
+            template.push_cell_json(
+                json!({ "tmp_type": "full", "capacity": capacity, "lock": lock, "type": null, "tmp_data": "0x0102" }),
+                Source::Output,
+                None,
+            );
+        }
+
+        let fee = template.balance(0, 1000);
+        assert_eq!(template.outputs.len(), 4);
+        // capacity(8) + code_hash(32) + hash_type(1) + data(2)
+        let auto = 43 * ONE_CKB;
+        assert_eq!(
+            template.outputs[3]["capacity"],
+            json!(1000 * ONE_CKB - auto - 142 * ONE_CKB - 100 * ONE_CKB - fee)
+        );
+        let tx = test_tx_with_fee(template.as_json());
+        assert_eq!(fee, tx.data().serialized_size_in_block() as u64);
+    }
+
+    #[test]
+    fn test_balance_with_registry() {
+        let lock = parse_json_script_to_mol("lock", &json!({ "code_hash": "{{always_success}}", "args": "0x01" }));
+        let out_point = OutPoint::new_builder().tx_hash(Byte32::new([1u8; 32])).build();
+        let mut registry = CellRegistry::new();
+        registry.insert_cell(
+            out_point.clone(),
+            CellOutput::new_builder()
+                .capacity((500 * ONE_CKB).pack())
+                .lock(lock.clone())
+                .build(),
+            bytes::Bytes::new(),
+            None,
+        );
+        registry.set_label("alice", out_point);
+
+        let mut template = init();
+        template.push_ref_input("alice", None);
+        template.push_output(
+            json!({
+                "capacity": "100 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }),
+            None,
+        );
+
+        let fee = template.balance_with_registry(1, 1000, &registry);
+        assert_eq!(template.outputs[1]["capacity"], json!(1400 * ONE_CKB - fee));
+        // The change goes back to the lock of the referenced cell.
+        assert_eq!(
+            parse_json_script_to_mol("outputs[1].lock", &template.outputs[1]["lock"]),
+            lock
+        );
+    }
+
+    #[test]
+    fn test_header_deps_time_literal() {
+        for literal in ["2024-01-01 00:00:00", "+30d"] {
+            let mut template = init();
+            template.push_header_deps(json!({ "number": 1, "timestamp": literal }));
+
+            // The header is the same as the one in a raw template, and the timestamp is in milliseconds.
+            let header = &template.as_json()["header_deps"][0];
+            let expected = TemplateParser::mock_block_header("header", &json!({ "number": 1, "timestamp": literal }));
+            assert_eq!(
+                TemplateParser::mock_block_header("header_deps[0]", header).unwrap(),
+                expected.unwrap()
+            );
+            let typed = TxTemplate::from_value(&template.as_json()).unwrap();
+            let timestamp = util::parse_json_timestamp("timestamp", &json!(literal), None) * 1000;
+            assert_eq!(typed.header_deps[0].timestamp, Some(timestamp));
+        }
+    }
+
+    struct CounterCellGenerator;
+
+    impl CellGenerator for CounterCellGenerator {
+        fn gen_cell(&self, version: u32, cell: Value) -> Value {
+            let count = cell["count"].as_u64().expect("cell.count is missing");
+            let mut data = vec![version as u8];
+            data.extend(count.to_le_bytes());
+
+            json!({
+                "capacity": "auto",
+                "lock": cell["lock"],
+                "type": cell["type"],
+                "data": util::bytes_to_hex(&data)
+            })
+        }
+    }
+
+    #[test]
+    fn test_cell_generator() {
+        register_cell_generator("test-counter", CounterCellGenerator);
+
+        let mut template = init();
+        template.push_contract_cell("test-counter", ContractType::Contract);
+        template.push_output(
+            json!({
+                "lock": { "code_hash": "{{always_success}}" },
+                "type": { "code_hash": "{{test-counter}}" },
+                "count": 3
+            }),
+            Some(2),
+        );
+
+        assert_eq!(template.outputs[0]["tmp_data"], json!("0x020300000000000000"));
+        // capacity(8) + lock(33) + type(33) + data(9)
+        assert_eq!(template.outputs[0]["capacity"], json!(83 * ONE_CKB));
+    }
+
+    #[test]
+    fn test_versioned_entity() {
+        let mut template = init();
+        for version in [1, 2] {
+            template.push_output(
+                json!({
+                    "capacity": "auto",
+                    "lock": { "code_hash": "{{always_success}}" },
+                    "entity": "0x0102"
+                }),
+                Some(version),
+            );
+        }
+
+        // Both versions are generated side by side, and they can be parsed by contracts.
+        for (i, version) in [1, 2].into_iter().enumerate() {
+            let data = util::parse_json_hex("tmp_data", &template.outputs[i]["tmp_data"]);
+            let entity = VersionedEntity::from_slice(&data).unwrap();
+            assert_eq!(entity.version, version);
+            assert_eq!(entity.payload, &[1, 2]);
+        }
+        // capacity(8) + lock(33) + version(4) + hash(32) + payload(2)
+        assert_eq!(template.outputs[0]["capacity"], json!(79 * ONE_CKB));
+    }
+}
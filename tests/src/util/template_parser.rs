@@ -2,63 +2,154 @@ use std::cell::Cell;
 use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
 use std::error::Error as StdError;
-use std::fmt::Debug;
 use std::fs::File;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::{env, fs};
 
-use ckb_chain_spec::consensus::{ConsensusBuilder, TYPE_ID_CODE_HASH};
 use ckb_mock_tx_types::*;
-use ckb_script::{TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_script::{ScriptError, ScriptGroupType, TransactionScriptsVerifier};
+use ckb_types::bytes;
 use ckb_types::core::cell::{resolve_transaction, ResolvedTransaction};
-use ckb_types::core::{Cycle, HeaderView, ScriptHashType, TransactionBuilder, TransactionView};
+use ckb_types::core::{Cycle, DepType, ExtraHashView, HeaderView, ScriptHashType, TransactionBuilder, TransactionView};
 use ckb_types::packed::*;
 use ckb_types::prelude::*;
-use ckb_types::{bytes, H256};
 use serde_json::Value;
 
+use super::binary_registry::{self, Binary};
+use super::cell_dump::{self, CellDump};
+use super::cell_registry::{CellRegistry, LiveCell};
+use super::chain_env::ChainEnv;
 use super::constants::*;
-use super::util;
-
-const BINARY_VERSION: &str = "BINARY_VERSION";
-
-pub enum BinaryVersion {
-    Debug,
-    Release,
+use super::error::{ErrorCode, ExecuteError, ExpectedFailure, ScriptFailure};
+use super::fee::{self, TxFee};
+use super::out_point::OutPointSpace;
+use super::signer::{self, Signer};
+use super::snapshot::{self, CycleSnapshot};
+use super::tx_lint::{self, LintLevel, LintMode};
+use super::type_id::{self, TYPE_ID_TABLE};
+use super::{template_schema, util};
+
+const DUMP_MOCK_TX: &str = "DUMP_MOCK_TX";
+const MOCK_TX_DIR: &str = "mock_txs";
+const TX_LINT: &str = "TX_LINT";
+
+pub enum DumpMode {
+    Off,
+    Failure,
+    Always,
 }
 
-impl FromStr for BinaryVersion {
+impl FromStr for DumpMode {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "debug" => Ok(BinaryVersion::Debug),
-            "release" => Ok(BinaryVersion::Release),
-            _ => Err("Environment variable BINARY_VERSION only support \"debug\" and \"release\"."),
+            "off" => Ok(DumpMode::Off),
+            "failure" => Ok(DumpMode::Failure),
+            "always" => Ok(DumpMode::Always),
+            _ => Err("Environment variable DUMP_MOCK_TX only support \"off\", \"failure\" and \"always\"."),
         }
     }
 }
 
-pub fn test_tx(tx: Value) {
-    println!("Transaction template: {}", serde_json::to_string_pretty(&tx).unwrap());
-    let mut parser = TemplateParser::from_data(tx, 350_000_000);
+fn dump_mode() -> DumpMode {
+    match env::var(DUMP_MOCK_TX) {
+        Ok(val) => val
+            .parse()
+            .expect("Dump mode should be one of off, failure and always."),
+        Err(_) => DumpMode::Failure,
+    }
+}
+
+/// The lint mode is decided by the environment variable TX_LINT.
+///
+/// - `off`: never lint.
+/// - `warn`: print the issues found before executing the transaction, this is the default mode.
+/// - `deny`: like `warn`, and the transaction fails without executing if there is any error.
+fn lint_mode() -> LintMode {
+    match env::var(TX_LINT) {
+        Ok(val) => val.parse().expect("Lint mode should be one of off, warn and deny."),
+        Err(_) => LintMode::Warn,
+    }
+}
+
+/// Dump the mock transaction to tests/mock_txs/<test_name>.json according to the environment variable DUMP_MOCK_TX.
+///
+/// - `off`: never dump.
+/// - `failure`: dump when the transaction does not behave as expected, this is the default mode.
+/// - `always`: dump after every execution.
+fn dump_mock_tx_if_needed(parser: &TemplateParser, is_failed: bool, failure: Option<&ScriptFailure>) {
+    let should_dump = match dump_mode() {
+        DumpMode::Off => false,
+        DumpMode::Failure => is_failed,
+        DumpMode::Always => true,
+    };
+    if !should_dump {
+        return;
+    }
+
+    let mut file_path = env::current_dir().unwrap();
+    file_path.push(MOCK_TX_DIR);
+    file_path.push(format!("{}.json", util::current_test_name().replace("::", "__")));
+
+    match parser.write_mock_tx(&file_path) {
+        Ok(_) => {
+            println!("Mock transaction is dumped to {}", file_path.display());
+            if let Some(failure) = failure {
+                println!("{}", failure.debugger_command(&file_path));
+            }
+        }
+        Err(err) => println!("Dump mock transaction to {} failed: {}", file_path.display(), err),
+    }
+}
+
+/// Execute the transaction and assert it passed, the transaction is returned so it can be committed to a MockChain.
+pub fn test_tx(tx: Value) -> TransactionView {
+    test_tx_with_parser(TemplateParser::from_data(tx, 350_000_000))
+}
+
+/// Like test_tx, but the parser is prepared by the caller, e.g. with a CellRegistry.
+pub fn test_tx_with_parser(parser: TemplateParser) -> TransactionView {
+    run_test_tx(parser, false)
+}
+
+fn run_test_tx(mut parser: TemplateParser, check_fee: bool) -> TransactionView {
+    println!(
+        "Transaction template: {}",
+        serde_json::to_string_pretty(&parser.template).unwrap()
+    );
     match parser.try_parse() {
         Ok(_) => match parser.execute_tx() {
             Ok((cycles, tx_view)) => {
+                let tx_fee = parser.tx_fee(cycles, fee::fee_rate());
                 println!(
                     r#"︎↑︎======================================↑︎
-Transaction size: {} bytes,
-   Suggested fee: {} shannon(feeRate: 1)
-          Cycles: {}
+{}
 ========================================"#,
-                    tx_view.data().total_size(),
-                    tx_view.data().total_size() + 4,
-                    cycles
+                    tx_fee
                 );
+                if check_fee {
+                    tx_fee.assert_enough();
+                }
+
+                dump_mock_tx_if_needed(&parser, false, None);
+                snapshot::check_cycle_snapshot(CycleSnapshot::new(
+                    tx_view.data().total_size(),
+                    cycles,
+                    parser.script_cycles(),
+                ));
+
+                tx_view
             }
             Err(e) => {
+                let failure = match &e {
+                    ExecuteError::Script(failure) => Some(failure.as_ref()),
+                    _ => None,
+                };
+                dump_mock_tx_if_needed(&parser, true, failure);
                 panic!(
                     "\n======\nThe transaction should pass the test, but it failed in script: {}\n======\n",
                     e.to_string()
@@ -74,28 +165,66 @@ Transaction size: {} bytes,
     }
 }
 
-pub fn challenge_tx(tx: Value, expected_error: impl Into<i8> + Clone + Debug) {
-    println!("Transaction template: {}", serde_json::to_string_pretty(&tx).unwrap());
-    let mut parser = TemplateParser::from_data(tx, 350_000_000);
-    let error_code: i8 = expected_error.clone().into();
+/// Like test_tx, and also assert the capacity of inputs minus outputs is enough for the fee at the FEE_RATE.
+pub fn test_tx_with_fee(tx: Value) -> TransactionView {
+    run_test_tx(TemplateParser::from_data(tx, 350_000_000), true)
+}
+
+/// Execute the transaction and assert it failed with the expected error.
+///
+/// The expected error can be an error enum of contracts in util::error, a `(contract, variant)` pair like
+/// `("playground", "ItemMissing")` or a raw i8 code.
+pub fn challenge_tx(tx: Value, expected_error: impl Into<ErrorCode>) {
+    challenge_tx_with(tx, expected_error, ExpectedFailure::default())
+}
+
+/// Like challenge_tx, but also assert on which script failed and at which cell.
+///
+/// ```json
+/// challenge_tx_with(template.as_json(), ScriptError::ItemMissing, ExpectedFailure::type_("playground").at(Source::Input, 0))
+/// ```
+pub fn challenge_tx_with(tx: Value, expected_error: impl Into<ErrorCode>, expected: ExpectedFailure) {
+    challenge_tx_with_parser(TemplateParser::from_data(tx, 350_000_000), expected_error, expected)
+}
+
+/// Like challenge_tx_with, but the parser is prepared by the caller, e.g. with a CellRegistry.
+pub fn challenge_tx_with_parser(
+    mut parser: TemplateParser,
+    expected_error: impl Into<ErrorCode>,
+    expected: ExpectedFailure,
+) {
+    println!(
+        "Transaction template: {}",
+        serde_json::to_string_pretty(&parser.template).unwrap()
+    );
+    let expected_error: ErrorCode = expected_error.into();
     match parser.try_parse() {
         Ok(_) => match parser.execute_tx() {
             Ok(_) => {
+                dump_mock_tx_if_needed(&parser, true, None);
                 panic!(
-                    "\n======\nThe test should failed with error code: {:?}({}), but it returns Ok.\n======\n",
-                    expected_error, error_code
+                    "\n======\nThe test should failed with error: {}, but it returns Ok.\n======\n",
+                    expected_error
                 );
             }
-            Err(err) => {
-                let msg = err.to_string();
-                println!("Error message(single code): {}", msg);
+            Err(ExecuteError::Script(failure)) => {
+                println!("Script failed:\n{}", failure);
 
-                let search = format!("error code {}", error_code);
+                let mismatches = expected.mismatches(&failure, &expected_error);
+                dump_mock_tx_if_needed(&parser, !mismatches.is_empty(), Some(&failure));
                 assert!(
-                    msg.contains(search.as_str()),
-                    "\n======\nThe test should failed with error code: {:?}({})\n======\n",
+                    mismatches.is_empty(),
+                    "\n======\nThe test should failed with error: {}, but:\n  - {}\n\nThe failed script:\n{}\n======\n",
                     expected_error,
-                    error_code
+                    mismatches.join("\n  - "),
+                    failure
+                );
+            }
+            Err(err) => {
+                dump_mock_tx_if_needed(&parser, true, None);
+                panic!(
+                    "\n======\nThe test should failed with error: {}, but: {}\n======\n",
+                    expected_error, err
                 );
             }
         },
@@ -111,11 +240,25 @@ pub fn challenge_tx(tx: Value, expected_error: impl Into<i8> + Clone + Debug) {
 pub struct TemplateParser {
     template: Value,
     type_id_map: HashMap<String, Byte32>,
+    // The data hash of binaries loaded in cell_deps, it is used to replace variables like {{data_hash:xxx}}.
+    data_hash_map: HashMap<String, Byte32>,
     tx_builder: Cell<TransactionBuilder>,
     mock_header_deps: Vec<HeaderView>,
+    // The block extensions of headers, they are keyed by the block hash like MockInfo.extensions.
+    mock_extensions: Vec<(Byte32, bytes::Bytes)>,
     mock_cell_deps: Vec<MockCellDep>,
     mock_inputs: Vec<MockInput>,
     max_cycles: u64,
+    script_cycles: Vec<(String, Cycle)>,
+    mock_tx: Option<MockTransaction>,
+    // The mock transaction imported from outside, if it exists the template will be ignored.
+    imported_mock_tx: Option<MockTransaction>,
+    // The cells and headers which can be referenced by out point or label in the template.
+    registry: CellRegistry,
+    env: ChainEnv,
+    lint_mode: LintMode,
+    // The inputs with `tmp_signer`, their lock groups are signed after the transaction is assembled.
+    signers: Vec<(usize, Signer)>,
 }
 
 impl TemplateParser {
@@ -125,11 +268,20 @@ impl TemplateParser {
         Ok(TemplateParser {
             template,
             type_id_map: TemplateParser::init_type_id_map(),
+            data_hash_map: HashMap::new(),
             tx_builder: Cell::new(TransactionBuilder::default()),
             mock_header_deps: vec![],
+            mock_extensions: vec![],
             mock_cell_deps: vec![],
             mock_inputs: vec![],
             max_cycles,
+            script_cycles: vec![],
+            mock_tx: None,
+            imported_mock_tx: None,
+            registry: CellRegistry::default(),
+            env: ChainEnv::default(),
+            lint_mode: lint_mode(),
+            signers: vec![],
         })
     }
 
@@ -141,11 +293,20 @@ impl TemplateParser {
         Ok(TemplateParser {
             template,
             type_id_map: TemplateParser::init_type_id_map(),
+            data_hash_map: HashMap::new(),
             tx_builder: Cell::new(TransactionBuilder::default()),
             mock_header_deps: vec![],
+            mock_extensions: vec![],
             mock_cell_deps: vec![],
             mock_inputs: vec![],
             max_cycles,
+            script_cycles: vec![],
+            mock_tx: None,
+            imported_mock_tx: None,
+            registry: CellRegistry::default(),
+            env: ChainEnv::default(),
+            lint_mode: lint_mode(),
+            signers: vec![],
         })
     }
 
@@ -153,27 +314,181 @@ impl TemplateParser {
         TemplateParser {
             template,
             type_id_map: TemplateParser::init_type_id_map(),
+            data_hash_map: HashMap::new(),
             tx_builder: Cell::new(TransactionBuilder::default()),
             mock_header_deps: vec![],
+            mock_extensions: vec![],
             mock_cell_deps: vec![],
             mock_inputs: vec![],
             max_cycles,
+            script_cycles: vec![],
+            mock_tx: None,
+            imported_mock_tx: None,
+            registry: CellRegistry::default(),
+            env: ChainEnv::default(),
+            lint_mode: lint_mode(),
+            signers: vec![],
         }
     }
 
-    fn init_type_id_map() -> HashMap<String, Byte32, RandomState> {
-        // The type IDs here are testing only.
-        let mut type_id_map = HashMap::new();
-        for (&key, &val) in TYPE_ID_TABLE.iter() {
-            type_id_map.insert(key.to_string(), util::hex_to_byte32(val).unwrap());
+    /// Resolve the cells referenced by `out_point` or `tmp_ref` in the template with the registry.
+    pub fn with_registry(mut self, registry: CellRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn registry(&self) -> &CellRegistry {
+        &self.registry
+    }
+
+    /// Verify the transaction in the environment, the `tmp_env` field of the template can still override it.
+    pub fn with_env(mut self, env: ChainEnv) -> Self {
+        self.env = env;
+        self
+    }
+
+    pub fn env(&self) -> &ChainEnv {
+        &self.env
+    }
+
+    /// Override the lint mode decided by the environment variable TX_LINT.
+    pub fn with_lint(mut self, lint_mode: LintMode) -> Self {
+        self.lint_mode = lint_mode;
+        self
+    }
+
+    /// Load a mock transaction directly, it is executed as it is and the template is ignored.
+    pub fn from_mock_tx(mock_tx: MockTransaction, max_cycles: u64) -> Self {
+        let mut parser = TemplateParser::from_data(Value::Null, max_cycles);
+        parser.imported_mock_tx = Some(mock_tx);
+        parser
+    }
+
+    /// Load a ReprMockTransaction JSON file, like the one dumped by TemplateParser::write_mock_tx or used by ckb-debugger.
+    pub fn from_mock_tx_file(filepath: String, max_cycles: u64) -> Result<Self, Box<dyn StdError>> {
+        let mut raw_json = String::new();
+        File::open(filepath)?.read_to_string(&mut raw_json)?;
+        let repr_tx: ReprMockTransaction = serde_json::from_str(&raw_json)?;
+
+        Ok(TemplateParser::from_mock_tx(repr_tx.into(), max_cycles))
+    }
+
+    /// Load a transaction in the RPC format, its inputs, cell_deps and header_deps are resolved from the cell dump.
+    pub fn from_rpc_tx(
+        tx: ckb_jsonrpc_types::Transaction,
+        dump: &CellDump,
+        max_cycles: u64,
+    ) -> Result<Self, Box<dyn StdError>> {
+        let mock_tx = dump.to_mock_tx(tx.into())?;
+        Ok(TemplateParser::from_mock_tx(mock_tx, max_cycles))
+    }
+
+    /// Load a transaction in the RPC format from file, the result of RPC get_transaction is also supported.
+    pub fn from_rpc_tx_file(
+        tx_filepath: String,
+        dump_filepath: String,
+        max_cycles: u64,
+    ) -> Result<Self, Box<dyn StdError>> {
+        let mut raw_json = String::new();
+        File::open(tx_filepath)?.read_to_string(&mut raw_json)?;
+        let tx = cell_dump::parse_rpc_tx(&raw_json)?;
+        let dump = CellDump::from_file(&dump_filepath)?;
+
+        TemplateParser::from_rpc_tx(tx, &dump, max_cycles)
+    }
+
+    /// Replace the data of the cell dep at the out point with a local binary, so an imported transaction can be verified
+    /// against a new version of the contract.
+    ///
+    /// The name will also be bound to the type hash of the cell dep, so the script will be reported by name. Be careful
+    /// that scripts referencing the cell dep by data hash will not match the new binary.
+    pub fn substitute_binary(
+        &mut self,
+        out_point: &OutPoint,
+        binary_name: &str,
+        is_deployed: bool,
+    ) -> Result<(), Box<dyn StdError>> {
+        let binary = binary_registry::load_binary(binary_name, is_deployed)?;
+        let mock_tx = self
+            .imported_mock_tx
+            .as_mut()
+            .ok_or("Only imported mock transactions support substituting binaries.")?;
+        let mock_cell_dep = mock_tx
+            .mock_info
+            .cell_deps
+            .iter_mut()
+            .find(|item| item.cell_dep.out_point() == *out_point)
+            .ok_or_else(|| {
+                format!(
+                    "Can not find cell dep {} to substitute with {}.",
+                    out_point, binary_name
+                )
+            })?;
+
+        mock_cell_dep.data = binary.data.clone();
+        if let Some(type_script) = mock_cell_dep.output.type_().to_opt() {
+            self.type_id_map
+                .insert(binary_name.to_string(), type_script.calc_script_hash());
         }
 
-        type_id_map
+        Ok(())
+    }
+
+    /// Like substitute_binary, but the cell dep is found by the contract instead of the out point.
+    pub fn substitute_contract(&mut self, binary_name: &str, is_deployed: bool) -> Result<(), Box<dyn StdError>> {
+        let out_point = self.find_contract_cell_dep(binary_name, is_deployed)?;
+        self.substitute_binary(&out_point, binary_name, is_deployed)
+    }
+
+    /// Find the out point of the contract in the cell deps of the imported transaction.
+    ///
+    /// A cell dep matches if its type script hash is the type ID of the contract, or if its data hash is the same as
+    /// the local binary.
+    pub fn find_contract_cell_dep(&self, binary_name: &str, is_deployed: bool) -> Result<OutPoint, Box<dyn StdError>> {
+        let binary = binary_registry::load_binary(binary_name, is_deployed)?;
+        let type_id = self.type_id_map.get(binary_name);
+        let mock_tx = self
+            .imported_mock_tx
+            .as_ref()
+            .ok_or("Only imported mock transactions support substituting binaries.")?;
+
+        mock_tx
+            .mock_info
+            .cell_deps
+            .iter()
+            .find(|item| {
+                let type_hash = item.output.type_().to_opt().map(|script| script.calc_script_hash());
+                (type_id.is_some() && type_hash.as_ref() == type_id)
+                    || CellOutput::calc_data_hash(&item.data) == binary.data_hash
+            })
+            .map(|item| item.cell_dep.out_point())
+            .ok_or_else(|| {
+                format!(
+                    "Can not find cell dep of {} by its type ID or data hash {}.",
+                    binary_name, binary.data_hash
+                )
+                .into()
+            })
+    }
+
+    fn init_type_id_map() -> HashMap<String, Byte32, RandomState> {
+        // The type IDs here are testing only.
+        TYPE_ID_TABLE.clone()
     }
 
     pub fn try_parse(&mut self) -> Result<(), Box<dyn StdError>> {
         let to_owned = |v: &Vec<Value>| -> Vec<Value> { v.to_owned() };
 
+        // The template is null when the transaction is imported from a mock transaction.
+        if !self.template.is_null() {
+            self.check_schema()?
+        }
+
+        if !self.template["tmp_env"].is_null() {
+            let env = self.template["tmp_env"].clone();
+            self.env.apply_json("tmp_env", &env)?
+        }
+
         if let Some(header_deps) = self.template["header_deps"].as_array().map(to_owned) {
             self.parse_header_deps(header_deps)?
         }
@@ -189,11 +504,205 @@ impl TemplateParser {
         if let Some(witnesses) = self.template["witnesses"].as_array().map(to_owned) {
             self.parse_witnesses(witnesses)?
         }
+        if !self.signers.is_empty() {
+            self.sign_witnesses()?
+        }
 
         Ok(())
     }
 
-    pub fn execute_tx(&mut self) -> Result<(Cycle, TransactionView), String> {
+    /// Sign the lock groups of inputs with `tmp_signer` like below, the witnesses are replaced with signed ones.
+    ///
+    /// ```json
+    /// {
+    ///     "previous_output": { ... },
+    ///     "since": 0,
+    ///     "tmp_signer": "alice" | { "multisig": MultisigConfig, "signers": [...] } // see signer::Signer
+    /// }
+    /// ```
+    ///
+    /// The transaction hash must be final, so the header deps are set the same way as build_mock_tx.
+    fn sign_witnesses(&mut self) -> Result<(), Box<dyn StdError>> {
+        let builder = self.tx_builder.take();
+        let header_hashes = self
+            .mock_header_deps
+            .iter()
+            .map(|header| header.hash())
+            .collect::<Vec<_>>();
+        let tx = builder.clone().set_header_deps(header_hashes).build();
+        let locks = self
+            .mock_inputs
+            .iter()
+            .map(|input| input.output.lock())
+            .collect::<Vec<_>>();
+
+        let witnesses = signer::sign_inputs(&tx, &locks, &self.signers)?;
+        self.tx_builder
+            .set(builder.set_witnesses(witnesses.into_iter().map(|witness| witness.pack()).collect()));
+
+        Ok(())
+    }
+
+    pub fn execute_tx(&mut self) -> Result<(Cycle, TransactionView), ExecuteError> {
+        let mock_tx = match self.imported_mock_tx.clone() {
+            Some(mock_tx) => mock_tx,
+            None => self.build_mock_tx(),
+        };
+        let tx = mock_tx.core_transaction();
+
+        self.mock_tx = Some(mock_tx.clone());
+
+        let consensus = self.env.consensus();
+        // Duplicated inputs can not be resolved, so errors found here are reported before resolving.
+        let mut lint_issues = tx_lint::check_tx(&tx);
+        if lint_issues.iter().any(|issue| issue.level == LintLevel::Error) {
+            self.check_lint(std::mem::take(&mut lint_issues))?;
+        }
+
+        let resource = Resource::from_both(&mock_tx, self.registry.clone()).map_err(ExecuteError::Resolve)?;
+        let rtx: ResolvedTransaction = {
+            let mut seen_inputs = HashSet::new();
+            resolve_transaction(tx.clone(), &mut seen_inputs, &resource, &resource)
+                .map_err(|err| ExecuteError::Resolve(format!("{:?}", err)))?
+        };
+        lint_issues.extend(tx_lint::check_resolved_tx(&rtx, &consensus));
+        self.check_lint(lint_issues)?;
+
+        let data_loader = DummyContext {
+            headers: mock_tx.mock_info.header_deps.clone(),
+            extensions: mock_tx.mock_info.extensions.clone(),
+        };
+
+        let tx_verify_env = self.env.tx_verify_env();
+        let mut verifier =
+            TransactionScriptsVerifier::new(rtx.into(), data_loader, consensus.into(), tx_verify_env.into());
+        // Keep the debug messages of each script, so the panic message can be reported when a script failed.
+        let messages: Arc<Mutex<Vec<(Byte32, String)>>> = Arc::new(Mutex::new(vec![]));
+        let messages_writer = messages.clone();
+        verifier.set_debug_printer(move |hash: &Byte32, message: &str| {
+            println!("Script(0x{}): {}", hex::encode(&hash.as_slice()[..6]), message);
+            if let Ok(mut messages) = messages_writer.lock() {
+                messages.push((hash.to_owned(), message.to_string()));
+            }
+        });
+
+        // Run script groups one by one like TransactionScriptsVerifier::verify does, so the cycles of each script can
+        // be recorded and the failed script group can be reported in detail.
+        let groups = verifier
+            .groups_with_type()
+            .map(|(group_type, hash, group)| (group_type, hash.to_owned(), group.to_owned()))
+            .collect::<Vec<_>>();
+        let mut cycles: Cycle = 0;
+        self.script_cycles.clear();
+        for (group_type, hash, group) in groups {
+            let ret = verifier
+                .verify_single(group_type, &hash, self.max_cycles - cycles)
+                .and_then(|used_cycles| {
+                    cycles
+                        .checked_add(used_cycles)
+                        .map(|total| (used_cycles, total))
+                        .ok_or(ScriptError::CyclesOverflow(cycles, used_cycles))
+                });
+
+            match ret {
+                Ok((used_cycles, total)) => {
+                    cycles = total;
+                    self.script_cycles
+                        .push((self.script_label(&group.script, group_type), used_cycles));
+                }
+                Err(err) => {
+                    let mut failure = ScriptFailure::new(
+                        self.script_name(&group.script),
+                        hash.clone(),
+                        group_type,
+                        group.input_indices.clone(),
+                        group.output_indices.clone(),
+                        err,
+                    );
+                    // The panic handler of ckb-std prints the PanicInfo which always starts with "panicked at".
+                    failure.panic_message = messages.lock().ok().and_then(|messages| {
+                        messages
+                            .iter()
+                            .rev()
+                            .find(|(script_hash, message)| *script_hash == hash && message.starts_with("panicked at"))
+                            .map(|(_, message)| message.to_owned())
+                    });
+
+                    return Err(ExecuteError::Script(Box::new(failure)));
+                }
+            }
+        }
+
+        Ok((cycles, tx))
+    }
+
+    /// The fee of the transaction executed last time, the inputs are the resolved cells in the mock transaction.
+    pub fn tx_fee(&self, cycles: Cycle, fee_rate: u64) -> TxFee {
+        let mock_tx = self
+            .mock_tx
+            .as_ref()
+            .expect("The transaction should be executed first.");
+        let inputs_capacity = mock_tx
+            .mock_info
+            .inputs
+            .iter()
+            .map(|input| Unpack::<u64>::unpack(&input.output.capacity()))
+            .sum::<u64>();
+
+        TxFee::new(&mock_tx.core_transaction(), inputs_capacity, cycles, fee_rate)
+    }
+
+    /// Validate the template against the schema, the unknown fields are printed as warnings unless the lint is off.
+    fn check_schema(&self) -> Result<(), Box<dyn StdError>> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = template_schema::validate(&self.template)
+            .into_iter()
+            .partition(|issue| issue.level == LintLevel::Error);
+
+        if self.lint_mode != LintMode::Off {
+            for issue in warnings.iter() {
+                println!("Schema {}", issue);
+            }
+        }
+        if !errors.is_empty() {
+            let details = errors
+                .iter()
+                .map(|issue| format!("  - {}: {}", issue.field, issue.message))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(format!("The template does not match the schema:\n{}", details).into());
+        }
+
+        Ok(())
+    }
+
+    /// Print the lint issues, and return them as an error if there is any error in the deny mode.
+    fn check_lint(&self, issues: Vec<tx_lint::LintIssue>) -> Result<(), ExecuteError> {
+        if self.lint_mode == LintMode::Off || issues.is_empty() {
+            return Ok(());
+        }
+
+        for issue in issues.iter() {
+            println!("Lint {}", issue);
+        }
+        if self.lint_mode == LintMode::Deny && issues.iter().any(|issue| issue.level == LintLevel::Error) {
+            return Err(ExecuteError::Lint(issues));
+        }
+
+        Ok(())
+    }
+
+    /// Parse the template and build the transaction without executing it, e.g. for calculating the size of it.
+    pub fn build_tx(&mut self) -> Result<TransactionView, Box<dyn StdError>> {
+        self.try_parse()?;
+        let mock_tx = match self.imported_mock_tx.clone() {
+            Some(mock_tx) => mock_tx,
+            None => self.build_mock_tx(),
+        };
+
+        Ok(mock_tx.core_transaction())
+    }
+
+    fn build_mock_tx(&mut self) -> MockTransaction {
         let mut builder = self.tx_builder.take();
         // The block hash of headers must be put into the header_deps field, then it will be readable later in the script.
         let mut header_hashes = Vec::new();
@@ -205,37 +714,70 @@ impl TemplateParser {
         let tx = builder.build();
 
         let mock_info = MockInfo {
-            header_deps: self.mock_header_deps.clone(),
+            header_deps: self.mock_header_deps.drain(0..).collect(),
             cell_deps: self.mock_cell_deps.drain(0..).collect(),
             inputs: self.mock_inputs.drain(0..).collect(),
-            extensions: vec![],
+            extensions: self.mock_extensions.drain(0..).collect(),
         };
-        let mock_tx = MockTransaction {
+
+        MockTransaction {
             mock_info,
             tx: tx.data(),
-        };
+        }
+    }
 
-        let resource = Resource::from_both(&mock_tx, DummyResourceLoader {})?;
-        let rtx: ResolvedTransaction = {
-            let mut seen_inputs = HashSet::new();
-            resolve_transaction(tx.clone(), &mut seen_inputs, &resource, &resource)
-                .map_err(|err| format!("Resolve transaction error: {:?}", err))?
-        };
-        let data_loader = DummyContext {
-            headers: self.mock_header_deps.drain(0..).collect(),
+    /// The mock transaction assembled in the last execution, it contains everything needed to run the transaction.
+    pub fn mock_tx(&self) -> Option<&MockTransaction> {
+        self.mock_tx.as_ref()
+    }
+
+    /// Write the mock transaction of the last execution as ReprMockTransaction JSON which can be loaded by ckb-debugger.
+    ///
+    /// All resolved cell deps, inputs and headers are included, and binaries are embedded as hex in cell data.
+    pub fn write_mock_tx(&self, file_path: &Path) -> Result<(), Box<dyn StdError>> {
+        let mock_tx = self
+            .mock_tx
+            .clone()
+            .ok_or("The transaction has not been executed, so there is no mock transaction.")?;
+        let repr_tx: ReprMockTransaction = mock_tx.into();
+
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(file_path, serde_json::to_string_pretty(&repr_tx)?)?;
+
+        Ok(())
+    }
+
+    /// The cycles consumed by each script group in the last execution, it is labeled like `playground.type`.
+    pub fn script_cycles(&self) -> &[(String, Cycle)] {
+        &self.script_cycles
+    }
+
+    /// Find the name of the script in the type ID map, or in the data hashes if the script is referenced by data hash.
+    fn script_name(&self, script: &Script) -> Option<String> {
+        let code_hash = script.code_hash();
+        let code_hash_map = if script.hash_type() == ScriptHashType::Type.into() {
+            &self.type_id_map
+        } else {
+            &self.data_hash_map
         };
 
-        let consensus = ConsensusBuilder::default().build();
-        let tx_verify_env = TxVerifyEnv::new_submit(&HeaderView::new_advanced_builder().epoch(0.pack()).build());
-        let mut verifier =
-            TransactionScriptsVerifier::new(rtx.into(), data_loader, consensus.into(), tx_verify_env.into());
-        verifier.set_debug_printer(Box::new(|hash: &Byte32, message: &str| {
-            println!("Script(0x{}): {}", hex::encode(&hash.as_slice()[..6]), message);
-        }));
+        code_hash_map
+            .iter()
+            .find(|(_, hash)| **hash == code_hash)
+            .map(|(name, _)| name.to_owned())
+    }
 
-        match verifier.verify(self.max_cycles) {
-            Ok(cycles) => Ok((cycles, tx)),
-            Err(err) => Err(format!("Verify script error: {:?}", err.to_string())),
+    /// Label the script with its name and group type, if the name is not found use a short code_hash instead.
+    fn script_label(&self, script: &Script, group_type: ScriptGroupType) -> String {
+        let name = self
+            .script_name(script)
+            .unwrap_or_else(|| format!("0x{}", hex::encode(&script.code_hash().as_slice()[..6])));
+
+        match group_type {
+            ScriptGroupType::Lock => format!("{}.lock", name),
+            ScriptGroupType::Type => format!("{}.type", name),
         }
     }
 
@@ -256,86 +798,83 @@ impl TemplateParser {
     ///         "proposals_hash": "0x1bc5abcadf5b34bcc85b00c4c4afd0d6b01d0c0ca11f8ca34241838d12b9df04",
     ///         "compact_target": "0x1d17319c",
     ///         "dao": "0xece20aa8185bb5368aedb6e329ee24001603723bfefdae0100290badf10d4507",
+    ///         "extension": "0x..." // The block extension, it can be loaded by the load_block_extension syscall.
     ///     },
     ///     ...
     /// ]
     /// ```
     ///
-    /// These fields are all optional, and it will be compiled to a RawHeader object in molecule finally.
+    /// These fields are all optional, and it will be compiled to a Header object in molecule finally. The `hash` field
+    /// is ignored, because the hash is always calculated from the other fields.
     fn parse_header_deps(&mut self, header_deps: Vec<Value>) -> Result<(), Box<dyn StdError>> {
         for (i, item) in header_deps.into_iter().enumerate() {
-            let header = self.mock_block_header(&format!("header_deps[{}]", i), &item)?;
-            self.mock_header_deps.push(header);
+            self.push_mock_header(&format!("header_deps[{}]", i), &item)?;
         }
 
         Ok(())
     }
 
+    /// The cell_deps should be an array of objects like below:
+    ///
+    /// ```json
+    /// [
+    ///     { "tmp_type": "contract" | "deployed_contract" | "shared_lib" | "deployed_shared_lib", "tmp_file_name": "..." },
+    ///     { "tmp_type": "full", "capacity": ..., "lock": ..., "type": ..., "tmp_data": ... },
+    ///     { "tmp_ref": "..." }, // or { "out_point": { ... } }, referencing a cell in the CellRegistry
+    ///     {
+    ///         "tmp_type": "dep_group",
+    ///         "tmp_members": [
+    ///             ... // Any of the above, they are mocked as cells but not pushed into the cell_deps of transaction
+    ///         ]
+    ///     },
+    ///     ...
+    /// ]
+    /// ```
     fn parse_cell_deps(&mut self, cell_deps: Vec<Value>) -> Result<(), Box<dyn StdError>> {
         let mut mocked_cell_deps = vec![];
 
         for (i, item) in cell_deps.into_iter().enumerate() {
-            match item["tmp_type"].as_str() {
-                Some("contract") | Some("deployed_contract") | Some("shared_lib") | Some("deployed_shared_lib") => {
-                    let tmp_type = item["tmp_type"].as_str().expect("The tmp_type field is required.");
-                    let is_deployed = if tmp_type.contains("deployed") { true } else { false };
-                    let is_shared_lib = if tmp_type.contains("shared_lib") { true } else { false };
-
-                    let name = item["tmp_file_name"].as_str().unwrap();
-                    let (_type_id, _out_point, cell_dep, cell_output, cell_data) =
-                        self.mock_contract(name, is_deployed, is_shared_lib, Some(i));
-                    // println!("{:>30}: {}", name, _type_id);
-
-                    let header_hash_opt = if !item["tmp_header"].is_null() {
-                        let header = self.mock_block_header(&format!("cell_deps[{}]", i), &item["tmp_header"])?;
-                        let hash = header.hash();
-                        self.mock_header_deps.push(header);
-                        Some(hash)
-                    } else {
-                        None
-                    };
-
-                    let mock_cell_dep = MockCellDep {
-                        cell_dep: cell_dep.clone(),
-                        output: cell_output,
-                        data: cell_data,
-                        header: header_hash_opt,
-                    };
-                    self.mock_cell_deps.push(mock_cell_dep);
+            let field_name = format!("cell_deps[{}]", i);
+            let cell_dep = match item["tmp_type"].as_str() {
+                Some("dep_group") => {
+                    let members = item["tmp_members"]
+                        .as_array()
+                        .ok_or_else(|| format!("Field `{}.tmp_members` is required.", field_name))?;
+
+                    let mut member_out_points = vec![];
+                    for (j, member) in members.iter().enumerate() {
+                        let member_field_name = format!("{}.tmp_members[{}]", field_name, j);
+                        if member["tmp_type"].as_str() == Some("dep_group") {
+                            return Err(format!("Field `{}` can not be a dep group either.", member_field_name).into());
+                        }
+                        let cell_dep = self.parse_cell_dep(&member_field_name, i, Some(j), member)?;
+                        member_out_points.push(cell_dep.out_point());
+                    }
 
-                    mocked_cell_deps.push(cell_dep);
-                }
-                Some("full") => {
-                    // If we use {{...}} variable in cell_deps, then the contract need to be put in the cell_deps either.
-                    // This is because variable is not a real code_hash, but everything needs code_hash here, so the
-                    // contract need to be loaded for calculating hash.
-                    let (capacity, lock_script, type_script, cell_data) = self
-                        .parse_cell(item.clone(), Source::CellDep)
-                        .map_err(|err| format!("Field `cell_deps[{}]` parse failed: {}", i, err.to_string()))?;
-
-                    // Generate static out point for debugging purposes.
-                    let out_point = self.mock_out_point(i);
-                    let cell_dep = CellDep::new_builder().out_point(out_point.clone()).build();
+                    let out_point = OutPointSpace::CellDep.out_point(i as u64);
+                    let cell_dep = CellDep::new_builder()
+                        .out_point(out_point)
+                        .dep_type(DepType::DepGroup.into())
+                        .build();
+                    let cell_data = OutPointVec::new_builder().set(member_out_points).build().as_bytes();
                     let cell_output = CellOutput::new_builder()
-                        .capacity(capacity.pack())
-                        .lock(lock_script)
-                        .type_(ScriptOpt::new_builder().set(type_script).build())
+                        .capacity(0u64.pack())
+                        .lock(Script::default())
                         .build();
 
-                    let mock_cell_dep = MockCellDep {
+                    self.mock_cell_deps.push(MockCellDep {
                         cell_dep: cell_dep.clone(),
                         output: cell_output,
                         data: cell_data,
                         header: None,
-                    };
-                    self.mock_cell_deps.push(mock_cell_dep);
+                    });
 
-                    mocked_cell_deps.push(cell_dep);
+                    cell_dep
                 }
-                _ => {
-                    return Err("Unsupported cell_deps type.".into());
-                }
-            }
+                _ => self.parse_cell_dep(&field_name, i, None, &item)?,
+            };
+
+            mocked_cell_deps.push(cell_dep);
         }
 
         let builder = self.tx_builder.take();
@@ -344,12 +883,109 @@ impl TemplateParser {
         Ok(())
     }
 
+    /// Mock a cell of cell_deps and return the CellDep referencing it.
+    ///
+    /// The mock out point is decided by the index in cell_deps, and also the index in the dep group for its members.
+    fn parse_cell_dep(
+        &mut self,
+        field_name: &str,
+        i: usize,
+        member_opt: Option<usize>,
+        item: &Value,
+    ) -> Result<CellDep, Box<dyn StdError>> {
+        let mock_out_point = |space: OutPointSpace| match member_opt {
+            Some(j) => OutPointSpace::DepGroupMember.out_point(((i as u64) << 32) | j as u64),
+            None => space.out_point(i as u64),
+        };
+        match item["tmp_type"].as_str() {
+            Some("contract") | Some("deployed_contract") | Some("shared_lib") | Some("deployed_shared_lib") => {
+                let tmp_type = item["tmp_type"].as_str().expect("The tmp_type field is required.");
+                let is_deployed = tmp_type.contains("deployed");
+                let is_shared_lib = tmp_type.contains("shared_lib");
+
+                let name = item["tmp_file_name"].as_str().unwrap();
+                let (type_id, cell_dep, cell_output, binary) = self.mock_contract(
+                    name,
+                    is_deployed,
+                    is_shared_lib,
+                    mock_out_point(OutPointSpace::ContractDep),
+                );
+                self.data_hash_map.insert(name.to_string(), binary.data_hash.clone());
+                self.type_id_map.insert(name.to_string(), type_id);
+
+                let header_hash_opt = if !item["tmp_header"].is_null() {
+                    Some(self.push_mock_header(field_name, &item["tmp_header"])?)
+                } else {
+                    None
+                };
+
+                let mock_cell_dep = MockCellDep {
+                    cell_dep: cell_dep.clone(),
+                    output: cell_output,
+                    data: binary.data.clone(),
+                    header: header_hash_opt,
+                };
+                self.mock_cell_deps.push(mock_cell_dep);
+
+                Ok(cell_dep)
+            }
+            Some("full") => {
+                // If we use {{...}} variable in cell_deps, then the contract need to be put in the cell_deps either.
+                // This is because variable is not a real code_hash, but everything needs code_hash here, so the
+                // contract need to be loaded for calculating hash.
+                let (capacity, lock_script, type_script, cell_data) =
+                    self.parse_cell(field_name, item.clone(), Source::CellDep)?;
+
+                // Use the real out point if the cell is live on a MockChain, otherwise generate static out point for
+                // debugging purposes.
+                let out_point = if !item["tmp_out_point"].is_null() {
+                    parse_out_point(&format!("{}.tmp_out_point", field_name), &item["tmp_out_point"])?
+                } else {
+                    mock_out_point(OutPointSpace::CellDep)
+                };
+                let cell_dep = CellDep::new_builder().out_point(out_point.clone()).build();
+                let cell_output = CellOutput::new_builder()
+                    .capacity(capacity.pack())
+                    .lock(lock_script)
+                    .type_(ScriptOpt::new_builder().set(type_script).build())
+                    .build();
+
+                let mock_cell_dep = MockCellDep {
+                    cell_dep: cell_dep.clone(),
+                    output: cell_output,
+                    data: cell_data,
+                    header: None,
+                };
+                self.mock_cell_deps.push(mock_cell_dep);
+
+                Ok(cell_dep)
+            }
+            None if is_cell_ref(item) => {
+                let (out_point, live_cell) = self.resolve_cell_ref(field_name, item)?;
+                let header_hash_opt = self.push_registered_header(field_name, &live_cell)?;
+
+                let cell_dep = CellDep::new_builder().out_point(out_point).build();
+                let mock_cell_dep = MockCellDep {
+                    cell_dep: cell_dep.clone(),
+                    output: live_cell.output,
+                    data: live_cell.data,
+                    header: header_hash_opt,
+                };
+                self.mock_cell_deps.push(mock_cell_dep);
+
+                Ok(cell_dep)
+            }
+            _ => Err(format!("Field `{}` has unsupported cell_deps type.", field_name).into()),
+        }
+    }
+
     /// The inputs should be an array of objects like below:
     ///
     /// ```json
     /// [
     ///     {
     ///         "previous_output": {
+    ///             "tmp_out_point": { "tx_hash": "0x...", "index": 0 }, // optional, the out point of a live cell on a MockChain
     ///             "capacity": ...,
     ///             "lock": ...,
     ///             "type": ...,
@@ -360,6 +996,11 @@ impl TemplateParser {
     ///             ... // The same as the header_deps
     ///         }
     ///     },
+    ///     {
+    ///         // The cell can also be referenced in the CellRegistry by { "out_point": { ... } } or { "tmp_ref": "<label>" }.
+    ///         "previous_output": { "tmp_ref": "..." },
+    ///         "since": "0x..."
+    ///     },
     ///     ...
     /// ]
     /// ```
@@ -369,33 +1010,37 @@ impl TemplateParser {
         let mut mocked_inputs = vec![];
 
         for (i, item) in inputs.into_iter().enumerate() {
+            if !item["tmp_signer"].is_null() {
+                let signer = Signer::from_json(&format!("inputs[{}].tmp_signer", i), &item["tmp_signer"])?;
+                self.signers.push((i, signer));
+            }
             match item["previous_output"]["tmp_type"].as_str() {
                 Some("full") => {
                     // parse inputs[].previous_output as a mock cell
-                    let (capacity, lock_script, type_script, cell_data) = self
-                        .parse_cell(item["previous_output"].clone(), Source::Input)
-                        .map_err(|err| {
-                            format!(
-                                "Field `inputs[{}].previous_output` parse failed: {}",
-                                i,
-                                err.to_string()
-                            )
-                        })?;
+                    let (capacity, lock_script, type_script, cell_data) = self.parse_cell(
+                        &format!("inputs[{}].previous_output", i),
+                        item["previous_output"].clone(),
+                        Source::Input,
+                    )?;
                     // parse inputs[].since
-                    let since = util::parse_json_u64(&format!("cell.inputs[{}].since", i), &item["since"], Some(0));
+                    let since = util::parse_json_u64(&format!("inputs[{}].since", i), &item["since"], Some(0));
 
                     let header_hash_opt = if !item["previous_output"]["tmp_header"].is_null() {
-                        let header =
-                            self.mock_block_header(&format!("inputs[{}]", i), &item["previous_output"]["tmp_header"])?;
-                        let hash = header.hash();
-                        self.mock_header_deps.push(header);
-                        Some(hash)
+                        Some(self.push_mock_header(&format!("inputs[{}]", i), &item["previous_output"]["tmp_header"])?)
                     } else {
                         None
                     };
 
-                    // Generate static out point for debugging purposes, and it use the space of 1_000_000 to u64::Max.
-                    let out_point = self.mock_out_point(i + 1_000_000);
+                    // Use the real out point if the cell is live on a MockChain, otherwise generate static out point for
+                    // debugging purposes.
+                    let out_point = if !item["previous_output"]["tmp_out_point"].is_null() {
+                        parse_out_point(
+                            &format!("inputs[{}].previous_output.tmp_out_point", i),
+                            &item["previous_output"]["tmp_out_point"],
+                        )?
+                    } else {
+                        OutPointSpace::Input.out_point(i as u64)
+                    };
                     let cell_input = CellInput::new_builder()
                         .previous_output(out_point.clone())
                         .since(since.pack())
@@ -416,8 +1061,32 @@ impl TemplateParser {
 
                     mocked_inputs.push(cell_input);
                 }
+                None if is_cell_ref(&item["previous_output"]) => {
+                    let field_name = format!("inputs[{}].previous_output", i);
+                    let (out_point, live_cell) = self.resolve_cell_ref(&field_name, &item["previous_output"])?;
+                    let header_hash_opt = self.push_registered_header(&field_name, &live_cell)?;
+                    let since = util::parse_json_u64(&format!("inputs[{}].since", i), &item["since"], Some(0));
+
+                    let cell_input = CellInput::new_builder()
+                        .previous_output(out_point)
+                        .since(since.pack())
+                        .build();
+                    let mock_input = MockInput {
+                        input: cell_input.clone(),
+                        output: live_cell.output,
+                        data: live_cell.data,
+                        header: header_hash_opt,
+                    };
+                    self.mock_inputs.push(mock_input);
+
+                    mocked_inputs.push(cell_input);
+                }
                 _ => {
-                    return Err("Unsupported inputs type.".into());
+                    return Err(format!(
+                        "Field `inputs[{}].previous_output` should be a full cell or a reference like {{ \"tmp_ref\": \"<label>\" }}.",
+                        i
+                    )
+                    .into());
                 }
             }
         }
@@ -437,9 +1106,8 @@ impl TemplateParser {
             match item["tmp_type"].as_str() {
                 Some("full") => {
                     // parse inputs[].previous_output as a mock cell
-                    let (capacity, lock_script, type_script, cell_data) = self
-                        .parse_cell(item.clone(), Source::Output)
-                        .map_err(|err| format!("Field `outputs[{}]` parse failed: {}", i, err.to_string()))?;
+                    let (capacity, lock_script, type_script, cell_data) =
+                        self.parse_cell(&format!("outputs[{}]", i), item.clone(), Source::Output)?;
 
                     let cell_output = CellOutput::new_builder()
                         .capacity(capacity.pack())
@@ -451,7 +1119,7 @@ impl TemplateParser {
                     mocked_outputs_data.push(cell_data);
                 }
                 _ => {
-                    return Err("Unsupported outputs type.".into());
+                    return Err(format!("Field `outputs[{}].tmp_type` should be \"full\".", i).into());
                 }
             }
         }
@@ -481,21 +1149,66 @@ impl TemplateParser {
         Ok(())
     }
 
+    /// Find the cell referenced by `{ "out_point": { ... } }` or `{ "tmp_ref": "<label>" }` in the registry.
+    fn resolve_cell_ref(&self, field_name: &str, cell_ref: &Value) -> Result<(OutPoint, LiveCell), Box<dyn StdError>> {
+        let out_point = if let Some(label) = cell_ref["tmp_ref"].as_str() {
+            self.registry.get_label(label).cloned().ok_or_else(|| {
+                format!(
+                    "Field `{}.tmp_ref` parse failed: label {} is not registered",
+                    field_name, label
+                )
+            })?
+        } else {
+            parse_out_point(&format!("{}.out_point", field_name), &cell_ref["out_point"])?
+        };
+        let live_cell = self.registry.get_cell(&out_point).cloned().ok_or_else(|| {
+            format!(
+                "Field `{}` parse failed: cell {} is not a live cell in the registry",
+                field_name, out_point
+            )
+        })?;
+
+        Ok((out_point, live_cell))
+    }
+
+    /// Push the header of the block which the registered cell is committed in into header_deps, so it can be loaded.
+    fn push_registered_header(
+        &mut self,
+        field_name: &str,
+        live_cell: &LiveCell,
+    ) -> Result<Option<Byte32>, Box<dyn StdError>> {
+        let hash = match &live_cell.header {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+        let header = self
+            .registry
+            .get_header(hash)
+            .cloned()
+            .ok_or_else(|| format!("Field `{}` parse failed: header {} is not registered", field_name, hash))?;
+        if !self.mock_header_deps.iter().any(|item| item.hash() == header.hash()) {
+            self.mock_header_deps.push(header);
+        }
+
+        Ok(Some(hash.to_owned()))
+    }
+
     fn parse_cell(
         &self,
+        field_name: &str,
         cell: Value,
         source: Source,
     ) -> Result<(u64, Script, Option<Script>, bytes::Bytes), Box<dyn StdError>> {
         // parse capacity of cell
-        let capacity = util::parse_json_u64("cell.capacity", &cell["capacity"], None);
+        let capacity = util::parse_json_capacity(&format!("{}.capacity", field_name), &cell["capacity"], None);
 
         // parse lock script and type script of cell
         let lock_script = self
             .parse_script(cell["lock"].clone(), source)
-            .map_err(|err| format!("Field `cell.lock` parse failed: {}", err.to_string()))?;
+            .map_err(|err| format!("Field `{}.lock` parse failed: {}", field_name, err))?;
         let type_script = self
             .parse_script(cell["type"].clone(), source)
-            .map_err(|err| format!("Field `cell.type` parse failed: {}", err.to_string()))?;
+            .map_err(|err| format!("Field `{}.type` parse failed: {}", field_name, err))?;
 
         // parse data of cell
         let data;
@@ -505,7 +1218,9 @@ impl TemplateParser {
             data = bytes::Bytes::new();
         }
 
-        Ok((capacity, lock_script.unwrap(), type_script, data))
+        let lock_script = lock_script.ok_or_else(|| format!("Field `{}.lock` is required.", field_name))?;
+
+        Ok((capacity, lock_script, type_script, data))
     }
 
     fn parse_script(&self, script_val: Value, source: Source) -> Result<Option<Script>, Box<dyn StdError>> {
@@ -517,7 +1232,20 @@ impl TemplateParser {
         if let Some(code_hash) = script_val["code_hash"].as_str() {
             // If code_hash is variable like {{xxx}}, then parse script field as deployed contract,
             let real_code_hash;
-            if let Some(caps) = RE_VARIABLE.captures(code_hash) {
+            if let Some(caps) = RE_DATA_HASH_VARIABLE.captures(code_hash) {
+                // If code_hash is variable like {{data_hash:xxx}}, then replace it with the data hash of the binary.
+                let script_name = caps.get(1).map(|m| m.as_str()).unwrap();
+                real_code_hash = match self.data_hash_map.get(script_name) {
+                    Some(code_hash) => code_hash.to_owned(),
+                    _ => {
+                        if source == Source::CellDep {
+                            Byte32::default()
+                        } else {
+                            return Err(format!("not found binary {} in cell_deps", script_name).into());
+                        }
+                    }
+                };
+            } else if let Some(caps) = RE_VARIABLE.captures(code_hash) {
                 let script_name = caps.get(1).map(|m| m.as_str()).unwrap();
                 real_code_hash = match self.type_id_map.get(script_name) {
                     Some(code_hash) => code_hash.to_owned(),
@@ -529,11 +1257,8 @@ impl TemplateParser {
                         }
                     }
                 };
-                // Tip: If contract can not find some cell by type ID, you can uncomment the following line to ensure transaction has correct type ID.
-                println!("Replace code_hash {} with {} .", script_name, real_code_hash);
-
-                // else parse script field by field.
             } else {
+                // else parse script field by field.
                 let code_hash_str: &str = script_val["code_hash"]
                     .as_str()
                     .expect("The code_hash field is required.");
@@ -543,6 +1268,22 @@ impl TemplateParser {
             let mut args: String = script_val["args"].as_str().unwrap_or("").to_string();
             if !args.is_empty() {
                 // If args is not empty, try to find and replace variables in args.
+                if let Some(caps) = RE_DATA_HASH_VARIABLE.captures(&args) {
+                    let script_name = caps.get(1).map(|m| m.as_str()).unwrap();
+                    let code_hash = if source == Source::CellDep {
+                        Byte32::default()
+                    } else {
+                        match self.data_hash_map.get(script_name) {
+                            Some(code_hash) => code_hash.to_owned(),
+                            _ => return Err(format!("not found binary {} in cell_deps", script_name).into()),
+                        }
+                    };
+
+                    args = args.replace(
+                        &format!("{{{{data_hash:{}}}}}", script_name),
+                        &hex_string(code_hash.as_reader().raw_data()),
+                    );
+                }
                 if let Some(caps) = RE_VARIABLE.captures(&args) {
                     let script_name = caps.get(1).map(|m| m.as_str()).unwrap();
                     let code_hash = if source == Source::CellDep {
@@ -562,8 +1303,17 @@ impl TemplateParser {
             }
 
             let hash_type = match script_val["hash_type"].as_str() {
+                Some("type") | None => ScriptHashType::Type,
                 Some("data") => ScriptHashType::Data,
-                _ => ScriptHashType::Type,
+                Some("data1") => ScriptHashType::Data1,
+                Some("data2") => ScriptHashType::Data2,
+                Some(val) => {
+                    return Err(format!(
+                        "Field `hash_type` only support \"type\", \"data\", \"data1\" and \"data2\", but got \"{}\".",
+                        val
+                    )
+                    .into())
+                }
             };
 
             script = Some(
@@ -580,42 +1330,83 @@ impl TemplateParser {
         Ok(script)
     }
 
-    fn mock_block_header(&self, field_name: &str, header: &Value) -> Result<HeaderView, Box<dyn StdError>> {
+    /// Build the header with the same fields as the template, so a header recorded elsewhere (like the blocks of a
+    /// MockChain) can be rebuilt with the same hash.
+    ///
+    /// If the `extension` field exists and the `extra_hash` field is omitted, the extra_hash is calculated from the
+    /// extension like a block without uncles, so the header can be used to load the extension.
+    pub fn mock_block_header(field_name: &str, header: &Value) -> Result<HeaderView, Box<dyn StdError>> {
         let version = util::parse_json_u32(&format!("{}.version", field_name), &header["version"], Some(0));
         let number = if header["number"].is_null() {
             util::parse_json_u64(&format!("{}.height", field_name), &header["height"], Some(0))
         } else {
             util::parse_json_u64(&format!("{}.number", field_name), &header["number"], Some(0))
         };
-        let timestamp = util::parse_json_u64(&format!("{}.timestamp", field_name), &header["timestamp"], Some(0));
+        let timestamp =
+            util::parse_json_timestamp_ms(&format!("{}.timestamp", field_name), &header["timestamp"], Some(0));
         let epoch = util::parse_json_u64(&format!("{}.epoch", field_name), &header["epoch"], Some(0));
-
-        let transactions_root_raw = util::parse_json_hex_with_default(
-            &format!("{}.transactions_root", field_name),
-            &header["transactions_root"],
-            vec![0u8; 32],
+        let compact_target = util::parse_json_u32(
+            &format!("{}.compact_target", field_name),
+            &header["compact_target"],
+            Some(0),
         );
-        let transactions_root = match Byte32::from_slice(&transactions_root_raw) {
-            Ok(transactions_root) => transactions_root,
-            Err(err) => return Err(format!("Parse transactions_root error: {:?}", err).into()),
+        let nonce = util::parse_json_u128(&format!("{}.nonce", field_name), &header["nonce"], Some(0));
+
+        let parent_hash = parse_header_hash(field_name, "parent_hash", header)?;
+        let transactions_root = parse_header_hash(field_name, "transactions_root", header)?;
+        let proposals_hash = parse_header_hash(field_name, "proposals_hash", header)?;
+        let dao = parse_header_hash(field_name, "dao", header)?;
+        let extra_hash = match TemplateParser::mock_block_extension(field_name, header)? {
+            Some(extension) if header["extra_hash"].is_null() => {
+                let uncles_hash = UncleBlockVec::default().calc_uncles_hash();
+                let extension_hash = CellOutput::calc_data_hash(&extension);
+                ExtraHashView::new(uncles_hash, Some(extension_hash)).extra_hash()
+            }
+            _ => parse_header_hash(field_name, "extra_hash", header)?,
         };
 
         let raw_header = RawHeaderBuilder::default()
             .version(version.pack())
+            .compact_target(compact_target.pack())
             .number(number.pack())
             .timestamp(timestamp.pack())
             .epoch(epoch.pack())
+            .parent_hash(parent_hash)
             .transactions_root(transactions_root)
+            .proposals_hash(proposals_hash)
+            .extra_hash(extra_hash)
+            .dao(dao)
             .build();
-        let header = Header::new_builder().raw(raw_header).nonce(Uint128::default()).build();
+        let header = Header::new_builder().raw(raw_header).nonce(nonce.pack()).build();
         let header_view = header.into_view();
 
         Ok(header_view)
     }
 
-    fn mock_out_point(&self, index: usize) -> OutPoint {
-        let tx_hash = index_to_byte32(index);
-        OutPoint::new_builder().index(0u32.pack()).tx_hash(tx_hash).build()
+    /// Parse the `extension` field of the header, it is the raw bytes of the block extension in hex.
+    pub fn mock_block_extension(field_name: &str, header: &Value) -> Result<Option<bytes::Bytes>, Box<dyn StdError>> {
+        if header["extension"].is_null() {
+            return Ok(None);
+        }
+
+        let extension = util::parse_json_hex(&format!("{}.extension", field_name), &header["extension"]);
+        if extension.is_empty() {
+            return Err(format!("Field `{}.extension` should not be empty.", field_name).into());
+        }
+
+        Ok(Some(bytes::Bytes::from(extension)))
+    }
+
+    /// Push the header and its extension, so they can be loaded by scripts, return the hash of the header.
+    fn push_mock_header(&mut self, field_name: &str, header: &Value) -> Result<Byte32, Box<dyn StdError>> {
+        let header_view = TemplateParser::mock_block_header(field_name, header)?;
+        let hash = header_view.hash();
+        if let Some(extension) = TemplateParser::mock_block_extension(field_name, header)? {
+            self.mock_extensions.push((hash.clone(), extension));
+        }
+        self.mock_header_deps.push(header_view);
+
+        Ok(hash)
     }
 
     fn mock_contract(
@@ -623,113 +1414,52 @@ impl TemplateParser {
         binary_name: &str,
         is_deployed: bool,
         _is_shared_lib: bool,
-        index_opt: Option<usize>,
-    ) -> (Byte32, OutPoint, CellDep, CellOutput, bytes::Bytes) {
-        let file = self.load_binary(binary_name, is_deployed);
-
-        let args = {
-            // Padding args to 32 bytes, because it is convenient to use 32 bytes as the real args are also 32 bytes.
-            let mut buf = [0u8; 32];
-            let len = buf.len();
-            let bytes = binary_name.as_bytes();
-            if bytes.len() >= len {
-                buf.copy_from_slice(&bytes[..32]);
-            } else {
-                let (_, right) = buf.split_at_mut(len - bytes.len());
-                right.copy_from_slice(bytes);
-            }
-
-            buf
-        };
-        let args_bytes = args.iter().map(|v| Byte::new(*v)).collect::<Vec<_>>();
-        let type_ = Script::new_builder()
-            .code_hash(Byte32::new_unchecked(bytes::Bytes::from(TYPE_ID_CODE_HASH.as_bytes())))
-            .hash_type(ScriptHashType::Type.into())
-            .args(Bytes::new_builder().set(args_bytes).build())
-            .build();
+        out_point: OutPoint,
+    ) -> (Byte32, CellDep, CellOutput, Arc<Binary>) {
+        let binary = self.load_binary(binary_name, is_deployed);
 
+        let type_ = type_id::type_id_script(binary_name);
         let code_hash = type_.calc_script_hash();
         let cell_output = CellOutput::new_builder()
             .capacity(0u64.pack())
             .lock(Script::default())
             .type_(ScriptOpt::new_builder().set(Some(type_)).build())
             .build();
-        // Uncomment the line below can print type ID of each script in unit tests.
-        println!("Creating mock type ID: name: {}, type_id: {}", binary_name, code_hash);
 
-        let out_point = self.mock_out_point(index_opt.unwrap_or(rand::random::<usize>()));
-        let cell_dep = CellDep::new_builder().out_point(out_point.clone()).build();
+        let cell_dep = CellDep::new_builder().out_point(out_point).build();
 
-        (code_hash, out_point, cell_dep, cell_output, file)
+        (code_hash, cell_dep, cell_output, binary)
     }
 
-    fn load_binary(&self, name: &str, is_deployed: bool) -> bytes::Bytes {
-        let current_dir = env::current_dir().unwrap();
-        let mut file_path = PathBuf::new();
-
-        if is_deployed {
-            file_path.push(current_dir);
-            file_path.push("..");
-            file_path.push("deployed-scripts");
-        } else {
-            let binary_version = match env::var(BINARY_VERSION) {
-                Ok(val) => val.parse().expect("Binary version should be one of debug and release."),
-                Err(_) => BinaryVersion::Debug,
-            };
-            let binary_dir = match binary_version {
-                BinaryVersion::Debug => "debug",
-                BinaryVersion::Release => "release",
-            };
-
-            file_path.push(current_dir);
-            file_path.push("..");
-            file_path.push("build");
-            file_path.push(binary_dir);
-        }
-
-        file_path.push(name);
-
-        println!("Loading binary {} from {} ...", name, file_path.as_path().display());
-
-        fs::read(file_path.as_path())
-            .expect(&format!(
-                "Can not load binary of {} from path {}.",
-                name,
-                file_path.display()
-            ))
-            .into()
+    fn load_binary(&self, name: &str, is_deployed: bool) -> Arc<Binary> {
+        binary_registry::load_binary(name, is_deployed).unwrap_or_else(|err| panic!("{}", err))
     }
 }
 
-fn index_to_byte32(index: usize) -> Byte32 {
-    let index_bytes = (index as u64).to_be_bytes().to_vec();
-    let padding_bytes = [
-        vec![0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-        index_bytes,
-    ]
-    .concat();
-
-    Byte32::from_slice(&padding_bytes).expect("The Byte32::from_slice(&tx_hash_bytes) should always succeed.")
+fn is_cell_ref(cell: &Value) -> bool {
+    !cell["out_point"].is_null() || !cell["tmp_ref"].is_null()
 }
 
-pub struct DummyResourceLoader {}
+/// Parse an out point like `{ "tx_hash": "0x...", "index": 0 }`.
+fn parse_out_point(field_name: &str, out_point: &Value) -> Result<OutPoint, Box<dyn StdError>> {
+    let tx_hash_raw = util::parse_json_hex(&format!("{}.tx_hash", field_name), &out_point["tx_hash"]);
+    let tx_hash = Byte32::from_slice(&tx_hash_raw)
+        .map_err(|err| format!("Field `{}.tx_hash` parse failed: {:?}", field_name, err))?;
+    let index = util::parse_json_u32(&format!("{}.index", field_name), &out_point["index"], None);
 
-impl MockResourceLoader for DummyResourceLoader {
-    fn get_header(&mut self, hash: H256) -> Result<Option<HeaderView>, String> {
-        return Err(format!("Header {:x} is missing!", hash));
-    }
+    Ok(OutPoint::new_builder().tx_hash(tx_hash).index(index.pack()).build())
+}
 
-    fn get_live_cell(
-        &mut self,
-        out_point: OutPoint,
-    ) -> Result<Option<(CellOutput, bytes::Bytes, Option<Byte32>)>, String> {
-        return Err(format!("Cell: {:?} is missing!", out_point));
-    }
+/// Parse a 32 bytes hash field of the header, it is all zero if omitted.
+fn parse_header_hash(field_name: &str, key: &str, header: &Value) -> Result<Byte32, Box<dyn StdError>> {
+    let raw = util::parse_json_hex_with_default(&format!("{}.{}", field_name, key), &header[key], vec![0u8; 32]);
+    Byte32::from_slice(&raw).map_err(|err| format!("Field `{}.{}` parse failed: {:?}", field_name, key, err).into())
 }
 
 #[derive(Debug, Clone)]
 pub struct DummyContext {
     headers: Vec<HeaderView>,
+    extensions: Vec<(Byte32, bytes::Bytes)>,
 }
 
 impl ckb_traits::CellDataProvider for DummyContext {
@@ -749,7 +1479,330 @@ impl ckb_traits::HeaderProvider for DummyContext {
 }
 
 impl ckb_traits::ExtensionProvider for DummyContext {
-    fn get_block_extension(&self, _hash: &Byte32) -> Option<Bytes> {
-        None
+    fn get_block_extension(&self, hash: &Byte32) -> Option<Bytes> {
+        self.extensions
+            .iter()
+            .find(|(block_hash, _)| block_hash == hash)
+            .map(|(_, extension)| extension.pack())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn always_success_template() -> Value {
+        json!({
+            "cell_deps": [
+                { "tmp_type": "deployed_contract", "tmp_file_name": "always_success" }
+            ],
+            "inputs": [{
+                "previous_output": {
+                    "tmp_type": "full",
+                    "capacity": "100 CKB",
+                    "lock": { "code_hash": "{{always_success}}" }
+                }
+            }],
+            "outputs": [{
+                "tmp_type": "full",
+                "capacity": "100 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }],
+            "witnesses": ["0x"]
+        })
+    }
+
+    fn execute_template(template: Value) -> TemplateParser {
+        let mut parser = TemplateParser::from_data(template, MAX_CYCLES);
+        parser.try_parse().expect("The template should be valid.");
+        parser.execute_tx().expect("The transaction should pass.");
+        parser
+    }
+
+    #[test]
+    fn test_import_mock_tx() {
+        let parser = execute_template(always_success_template());
+        let repr_tx: ReprMockTransaction = parser.mock_tx().cloned().unwrap().into();
+        let raw_json = serde_json::to_string(&repr_tx).unwrap();
+
+        let repr_tx: ReprMockTransaction = serde_json::from_str(&raw_json).unwrap();
+        let mut parser = TemplateParser::from_mock_tx(repr_tx.into(), MAX_CYCLES);
+        parser.try_parse().unwrap();
+        assert!(parser.execute_tx().is_ok());
+    }
+
+    #[test]
+    fn test_substitute_contract() {
+        let parser = execute_template(always_success_template());
+        let mut mock_tx = parser.mock_tx().cloned().unwrap();
+        let out_point = mock_tx.mock_info.cell_deps[0].cell_dep.out_point();
+        // Break the binary, so the cell dep can only be found by its type ID.
+        mock_tx.mock_info.cell_deps[0].data = bytes::Bytes::from(vec![0u8; 4]);
+
+        let mut parser = TemplateParser::from_mock_tx(mock_tx, MAX_CYCLES);
+        assert_eq!(
+            parser.find_contract_cell_dep("always_success", true).unwrap(),
+            out_point
+        );
+        assert!(parser.find_contract_cell_dep("secp256k1_data", true).is_err());
+        parser.substitute_contract("always_success", true).unwrap();
+        parser.try_parse().unwrap();
+        assert!(parser.execute_tx().is_ok());
+    }
+
+    #[test]
+    fn test_resolve_cell_ref() {
+        let out_point = OutPoint::new(Byte32::new([1u8; 32]), 0);
+        let output = CellOutput::new_builder().capacity(100u64.pack()).build();
+        let mut registry = CellRegistry::new();
+        registry.insert_cell(out_point.clone(), output.clone(), bytes::Bytes::new(), None);
+        registry.set_label("alice", out_point.clone());
+        let parser = TemplateParser::from_data(Value::Null, MAX_CYCLES).with_registry(registry);
+
+        let (found, cell) = parser
+            .resolve_cell_ref("inputs[0].previous_output", &json!({ "tmp_ref": "alice" }))
+            .unwrap();
+        assert_eq!(found, out_point);
+        assert_eq!(cell.output, output);
+
+        let cell_ref = json!({ "out_point": { "tx_hash": format!("0x{}", "01".repeat(32)), "index": 0 } });
+        let (found, _) = parser.resolve_cell_ref("cell_deps[0]", &cell_ref).unwrap();
+        assert_eq!(found, out_point);
+
+        let err = parser
+            .resolve_cell_ref("inputs[0].previous_output", &json!({ "tmp_ref": "bob" }))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Field `inputs[0].previous_output.tmp_ref` parse failed: label bob is not registered"
+        );
+
+        let cell_ref = json!({ "out_point": { "tx_hash": format!("0x{}", "01".repeat(32)), "index": 1 } });
+        let err = parser.resolve_cell_ref("cell_deps[0]", &cell_ref).unwrap_err();
+        assert!(
+            err.to_string().contains("is not a live cell in the registry"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_dump_mock_tx() {
+        let parser = execute_template(always_success_template());
+        let tx_hash = parser.mock_tx().unwrap().core_transaction().hash();
+
+        let mut file_path = env::temp_dir();
+        file_path.push(format!("{}-{}", MOCK_TX_DIR, std::process::id()));
+        file_path.push("test_dump_mock_tx.json");
+        parser.write_mock_tx(&file_path).unwrap();
+
+        let mut parser =
+            TemplateParser::from_mock_tx_file(file_path.to_string_lossy().to_string(), MAX_CYCLES).unwrap();
+        fs::remove_dir_all(file_path.parent().unwrap()).unwrap();
+        assert_eq!(parser.build_tx().unwrap().hash(), tx_hash);
+        assert!(parser.execute_tx().is_ok());
+    }
+
+    #[test]
+    fn test_import_rpc_tx() {
+        let parser = execute_template(always_success_template());
+        let mock_tx = parser.mock_tx().cloned().unwrap();
+
+        let mut dump = CellDump::default();
+        for input in mock_tx.mock_info.inputs.iter() {
+            dump.cells.push(cell_dump::DumpedCell {
+                out_point: input.input.previous_output().into(),
+                output: input.output.clone().into(),
+                data: ckb_jsonrpc_types::JsonBytes::from_bytes(input.data.clone()),
+                header: None,
+            });
+        }
+        for cell_dep in mock_tx.mock_info.cell_deps.iter() {
+            dump.cells.push(cell_dump::DumpedCell {
+                out_point: cell_dep.cell_dep.out_point().into(),
+                output: cell_dep.output.clone().into(),
+                data: ckb_jsonrpc_types::JsonBytes::from_bytes(cell_dep.data.clone()),
+                header: None,
+            });
+        }
+
+        let out_point = mock_tx.mock_info.cell_deps[0].cell_dep.out_point();
+        let mut parser = TemplateParser::from_rpc_tx(mock_tx.tx.into(), &dump, MAX_CYCLES).unwrap();
+        parser.substitute_binary(&out_point, "always_success", true).unwrap();
+        assert!(parser.execute_tx().is_ok());
+    }
+
+    #[test]
+    fn test_dep_group() {
+        let mut template = always_success_template();
+        template["cell_deps"] = json!([{
+            "tmp_type": "dep_group",
+            "tmp_members": [
+                { "tmp_type": "deployed_contract", "tmp_file_name": "always_success" }
+            ]
+        }]);
+
+        let parser = execute_template(template);
+        let mock_tx = parser.mock_tx().unwrap();
+        let cell_deps = mock_tx.tx.raw().cell_deps();
+        assert_eq!(cell_deps.len(), 1);
+        assert_eq!(cell_deps.get(0).unwrap().dep_type(), DepType::DepGroup.into());
+        assert_eq!(mock_tx.mock_info.cell_deps.len(), 2);
+    }
+
+    #[test]
+    fn test_data_hash_variable() {
+        let mut template = always_success_template();
+        template["inputs"][0]["previous_output"]["lock"] = json!({
+            "code_hash": "{{data_hash:always_success}}",
+            "hash_type": "data",
+            "args": "0x{{data_hash:always_success}}00"
+        });
+        execute_template(template.clone());
+
+        template["inputs"][0]["previous_output"]["lock"]["hash_type"] = json!("data3");
+        let mut parser = TemplateParser::from_data(template, MAX_CYCLES);
+        assert!(parser.try_parse().is_err());
+    }
+
+    #[test]
+    fn test_vm_version_env() {
+        let mut template = always_success_template();
+        template["inputs"][0]["previous_output"]["lock"] = json!({
+            "code_hash": "{{data_hash:always_success}}",
+            "hash_type": "data2"
+        });
+
+        // The VM version 2 is not active in the default environment.
+        let mut parser = TemplateParser::from_data(template.clone(), MAX_CYCLES);
+        parser.try_parse().unwrap();
+        assert!(matches!(
+            parser.execute_tx(),
+            Err(ExecuteError::Script(failure)) if matches!(failure.cause, ScriptError::InvalidVmVersion(2))
+        ));
+
+        let mut parser = TemplateParser::from_data(template.clone(), MAX_CYCLES).with_env(ChainEnv::with_vm_version(2));
+        parser.try_parse().unwrap();
+        assert!(parser.execute_tx().is_ok());
+
+        template["tmp_env"] = json!({
+            "tip": { "number": HEIGHT, "epoch": 10 },
+            "hardforks": { "ckb2021": 0, "ckb2023": 10 }
+        });
+        execute_template(template);
+    }
+
+    #[test]
+    fn test_block_header_fields() {
+        let header_json = json!({
+            "version": 0,
+            "compact_target": "0x1d17319c",
+            "number": HEIGHT,
+            "timestamp": TIMESTAMP * 1000,
+            "epoch": "0x50903410008fb",
+            "parent_hash": "0x9fed43a51ae94c039e29602b46d25483c7b6a46cbce48559a3040440e6c12d5d",
+            "transactions_root": "0xd5439ebffae718cab0fc837fb7b03a06253c250bcae8a2933ac820580a675560",
+            "proposals_hash": "0x1bc5abcadf5b34bcc85b00c4c4afd0d6b01d0c0ca11f8ca34241838d12b9df04",
+            "dao": "0xece20aa8185bb5368aedb6e329ee24001603723bfefdae0100290badf10d4507",
+            "nonce": "0xb13000f771a12a98d82d62d2d6dfe382",
+            "extension": "0x0102030405"
+        });
+        let header = TemplateParser::mock_block_header("header", &header_json).unwrap();
+        assert_eq!(header.compact_target(), 0x1d17319c);
+        assert_eq!(header.nonce(), 0xb13000f771a12a98d82d62d2d6dfe382);
+        assert_eq!(format!("{:#x}", header.dao()), header_json["dao"].as_str().unwrap());
+
+        // The extra_hash should be the same as a block which contains the extension and no uncles.
+        let block = ckb_types::core::BlockView::new_advanced_builder()
+            .extension(Some([1u8, 2, 3, 4, 5].pack()))
+            .build_unchecked();
+        assert_eq!(header.extra_hash(), block.calc_extra_hash().extra_hash());
+
+        let mut template = always_success_template();
+        template["header_deps"] = json!([header_json]);
+        let parser = execute_template(template);
+        let mock_info = &parser.mock_tx().unwrap().mock_info;
+        assert_eq!(mock_info.header_deps[0].hash(), header.hash());
+        assert_eq!(
+            mock_info.extensions,
+            vec![(header.hash(), bytes::Bytes::from(vec![1u8, 2, 3, 4, 5]))]
+        );
+    }
+
+    #[test]
+    fn test_tx_lint() {
+        let mut template = always_success_template();
+        template["cell_deps"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "tmp_type": "deployed_shared_lib", "tmp_file_name": "secp256k1_data" }));
+        template["outputs"][0]["capacity"] = json!("200 CKB");
+        template["outputs"][0]["type"] = json!({
+            "code_hash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "hash_type": "data"
+        });
+        template["witnesses"] = json!([]);
+
+        let mut parser = TemplateParser::from_data(template, MAX_CYCLES).with_lint(LintMode::Deny);
+        parser.try_parse().unwrap();
+        let issues = match parser.execute_tx() {
+            Err(ExecuteError::Lint(issues)) => issues,
+            ret => panic!(
+                "The transaction should fail in lint, but got {:?}",
+                ret.map(|(cycles, _)| cycles)
+            ),
+        };
+        let fields = issues
+            .iter()
+            .map(|issue| (issue.level, issue.field.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                (LintLevel::Warning, "witnesses"),
+                (LintLevel::Error, "outputs"),
+                (LintLevel::Error, "outputs[0].type"),
+                (LintLevel::Warning, "cell_deps[1]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_schema() {
+        let mut template = always_success_template();
+        template["outputs"][0]["lock"]["hash_type"] = json!("data3");
+
+        let mut parser = TemplateParser::from_data(template, MAX_CYCLES);
+        let err = parser.try_parse().unwrap_err().to_string();
+        assert!(err.contains("/outputs/0/lock/hash_type"), "{}", err);
+        assert!(err.contains("data3"), "{}", err);
+    }
+
+    #[test]
+    fn test_stable_tx_hash() {
+        let tx_hash = |template: Value| {
+            let mut parser = TemplateParser::from_data(template, MAX_CYCLES);
+            parser.build_tx().unwrap().hash()
+        };
+
+        let mut template = always_success_template();
+        template["cell_deps"].as_array_mut().unwrap().push(json!({
+            "tmp_type": "full",
+            "capacity": 0,
+            "lock": { "code_hash": "{{always_success}}" }
+        }));
+        assert_eq!(tx_hash(template.clone()), tx_hash(template.clone()));
+
+        // Contracts and full cells in cell_deps have different out points even at the same index.
+        let tx = TemplateParser::from_data(template, MAX_CYCLES).build_tx().unwrap();
+        let cell_deps = tx.cell_deps().into_iter().collect::<Vec<_>>();
+        assert_eq!(cell_deps[0].out_point(), OutPointSpace::ContractDep.out_point(0));
+        assert_eq!(cell_deps[1].out_point(), OutPointSpace::CellDep.out_point(1));
+        assert_eq!(
+            tx.inputs().get(0).unwrap().previous_output(),
+            OutPointSpace::Input.out_point(0)
+        );
     }
 }
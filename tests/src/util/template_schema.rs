@@ -0,0 +1,146 @@
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::{Draft, JSONSchema};
+use lazy_static::lazy_static;
+use serde_json::Value;
+
+use super::tx_lint::LintIssue;
+
+/// The JSON schema of templates, it is also published at tests/schemas/template.schema.json for editors.
+pub const TEMPLATE_SCHEMA: &str = include_str!("../../schemas/template.schema.json");
+
+lazy_static! {
+    static ref COMPILED_SCHEMA: JSONSchema = {
+        let schema: Value = serde_json::from_str(TEMPLATE_SCHEMA).expect("The template schema should be valid JSON.");
+        JSONSchema::options()
+            .with_draft(Draft::Draft7)
+            .compile(&schema)
+            .expect("The template schema should be a valid draft-07 schema.")
+    };
+}
+
+/// Validate the template against the schema before parsing it.
+///
+/// The field of each issue is the JSON pointer of the value, like `/inputs/0/previous_output/lock/code_hash`, and the
+/// message contains the received value and the expected shape. Unknown fields are warnings because the parser simply
+/// ignores them, the others are errors.
+pub fn validate(template: &Value) -> Vec<LintIssue> {
+    let errors = match COMPILED_SCHEMA.validate(template) {
+        Ok(()) => return vec![],
+        Err(errors) => errors,
+    };
+
+    let mut issues = vec![];
+    for err in errors {
+        let pointer = err.instance_path.to_string();
+        match &err.kind {
+            ValidationErrorKind::AdditionalProperties { unexpected } => {
+                for key in unexpected.iter() {
+                    issues.push(LintIssue::warning(
+                        format!("{}/{}", pointer, key),
+                        "Unknown field, it is ignored by the parser.",
+                    ));
+                }
+            }
+            _ => {
+                let field = if pointer.is_empty() { String::from("/") } else { pointer };
+                issues.push(LintIssue::error(field, err.to_string()));
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::super::tx_lint::LintLevel;
+    use super::*;
+
+    fn template() -> Value {
+        json!({
+            "cell_deps": [
+                { "tmp_type": "contract", "tmp_file_name": "playground" },
+                { "tmp_ref": "always-success" }
+            ],
+            "inputs": [
+                {
+                    "previous_output": {
+                        "tmp_type": "full",
+                        "capacity": "142 CKB",
+                        "lock": { "code_hash": "{{always-success}}", "args": "0x" },
+                        "type": null,
+                        "tmp_header": null,
+                        "tmp_label": null
+                    },
+                    "since": "0x0",
+                    "tmp_signer": "alice"
+                }
+            ],
+            "outputs": [
+                {
+                    "tmp_type": "full",
+                    "capacity": 14_200_000_000u64,
+                    "lock": { "code_hash": "{{always-success}}", "hash_type": "type", "args": "0x0102" },
+                    "tmp_data": "0x0102"
+                }
+            ],
+            "witnesses": ["0x"],
+            "tmp_env": { "hardforks": { "ckb2021": true, "ckb2023": 10 } }
+        })
+    }
+
+    #[test]
+    fn test_valid_template() {
+        assert_eq!(validate(&template()), vec![]);
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        let mut template = template();
+        template["inputs"][0]["previous_output"]["lock"]["code_hash"] = json!(1);
+        template["outputs"][0]["capacity"] = json!("142 BTC");
+        template["cell_deps"][0]["tmp_file"] = json!("playground");
+
+        let issues = validate(&template);
+        let fields = issues
+            .iter()
+            .map(|issue| (issue.level, issue.field.as_str()))
+            .collect::<Vec<_>>();
+        assert!(fields.contains(&(LintLevel::Error, "/inputs/0/previous_output/lock/code_hash")));
+        assert!(fields.contains(&(LintLevel::Error, "/outputs/0/capacity")));
+        assert!(fields.contains(&(LintLevel::Warning, "/cell_deps/0/tmp_file")));
+
+        let issue = issues
+            .iter()
+            .find(|issue| issue.field == "/outputs/0/capacity")
+            .unwrap();
+        assert!(issue.message.contains("142 BTC"), "{}", issue.message);
+    }
+
+    #[test]
+    fn test_hex_with_variables() {
+        // The args should be pairs of hex digits, the variables are substituted as a whole.
+        let mut template = template();
+        template["outputs"][0]["lock"]["args"] = json!("0x{{data_hash:always-success}}00");
+        assert_eq!(validate(&template), vec![]);
+
+        template["outputs"][0]["lock"]["args"] = json!("0x123");
+        let issues = validate(&template);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].level, LintLevel::Error);
+        assert_eq!(issues[0].field, "/outputs/0/lock/args");
+    }
+
+    #[test]
+    fn test_header_hash() {
+        // The header from RPC contains the hash, it is ignored by the parser but not an unknown field.
+        let mut template = template();
+        template["header_deps"] = json!([{
+            "number": "0x1",
+            "hash": "0x24e33cfffb2658d32ed61b55ee156ad7288e0980b72416bf3a9ce11ecc2c737a"
+        }]);
+        assert_eq!(validate(&template), vec![]);
+    }
+}
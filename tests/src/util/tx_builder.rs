@@ -0,0 +1,654 @@
+//! Typed templates of transactions, they are serialized to exactly the same JSON as the templates written by hand, so
+//! the compiler catches typos of fields while the JSON templates keep working.
+//!
+//! ```json
+//! let mut tx = TxTemplate::new();
+//! tx.push_cell_dep(CellDepTemplate::contract("always_success", ContractType::DeployedContract));
+//! tx.push_input(InputTemplate::new(CellTemplate::new(100 * ONE_CKB, ScriptTemplate::by_name("always_success"))));
+//! tx.push_output(CellTemplate::new(100 * ONE_CKB, ScriptTemplate::by_name("always_success")).data(&[1, 2]));
+//! tx.push_witness(&[]);
+//! test_tx(tx.to_value());
+//! ```
+
+use ckb_types::packed::{Byte32, OutPoint};
+use ckb_types::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use super::signer::Signer;
+use super::template_generator::ContractType;
+use super::util;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashType {
+    #[default]
+    Type,
+    Data,
+    Data1,
+    Data2,
+}
+
+/// The script in templates, the code_hash and args could contain variables like `{{name}}` or `{{data_hash:name}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptTemplate {
+    pub code_hash: String,
+    #[serde(default)]
+    pub hash_type: HashType,
+    #[serde(default)]
+    pub args: String,
+}
+
+impl ScriptTemplate {
+    pub fn new(code_hash: &Byte32, hash_type: HashType) -> Self {
+        ScriptTemplate {
+            code_hash: format!("{:#x}", code_hash),
+            hash_type,
+            args: String::new(),
+        }
+    }
+
+    /// Reference the contract by its type ID, it is `{{name}}` in JSON.
+    pub fn by_name(name: &str) -> Self {
+        ScriptTemplate {
+            code_hash: format!("{{{{{}}}}}", name),
+            hash_type: HashType::Type,
+            args: String::new(),
+        }
+    }
+
+    /// Reference the contract by its data hash, it is `{{data_hash:name}}` in JSON.
+    pub fn by_data_hash(name: &str, hash_type: HashType) -> Self {
+        ScriptTemplate {
+            code_hash: format!("{{{{data_hash:{}}}}}", name),
+            hash_type,
+            args: String::new(),
+        }
+    }
+
+    pub fn args(mut self, args: &[u8]) -> Self {
+        self.args = util::bytes_to_hex(args);
+        self
+    }
+
+    /// Set the args in hex directly, so it could contain variables like `0x{{data_hash:name}}00`.
+    pub fn args_hex(mut self, args: &str) -> Self {
+        self.args = args.to_string();
+        self
+    }
+}
+
+/// The header in header_deps, `tmp_header` of cells and `tmp_env.tip`, the omitted fields are zero.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderTemplate {
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "de_opt_u32")]
+    pub version: Option<u32>,
+    #[serde(
+        default,
+        alias = "height",
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "de_opt_u64"
+    )]
+    pub number: Option<u64>,
+    /// The timestamp in milliseconds.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "de_opt_timestamp_ms"
+    )]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "de_opt_u64")]
+    pub epoch: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "de_opt_u32")]
+    pub compact_target: Option<u32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "ser_opt_u128",
+        deserialize_with = "de_opt_u128"
+    )]
+    pub nonce: Option<u128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transactions_root: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proposals_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dao: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    /// The hash is accepted so headers from RPC can be used as is, it is ignored like TemplateParser does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl HeaderTemplate {
+    pub fn new(number: u64, timestamp_ms: u64) -> Self {
+        HeaderTemplate {
+            number: Some(number),
+            timestamp: Some(timestamp_ms),
+            ..Default::default()
+        }
+    }
+
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    pub fn extension(mut self, extension: &[u8]) -> Self {
+        self.extension = Some(util::bytes_to_hex(extension));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutPointTemplate {
+    pub tx_hash: String,
+    #[serde(deserialize_with = "de_u32")]
+    pub index: u32,
+}
+
+impl From<&OutPoint> for OutPointTemplate {
+    fn from(out_point: &OutPoint) -> Self {
+        OutPointTemplate {
+            tx_hash: format!("{:#x}", out_point.tx_hash()),
+            index: out_point.index().unpack(),
+        }
+    }
+}
+
+/// A cell registered in the CellRegistry, it is referenced by out point or by label.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum CellRef {
+    OutPoint { out_point: OutPointTemplate },
+    Label { tmp_ref: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum FullCell {
+    #[default]
+    #[serde(rename = "full")]
+    Full,
+}
+
+/// A cell described field by field, it is `{ "tmp_type": "full", ... }` in JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CellTemplate {
+    tmp_type: FullCell,
+    #[serde(deserialize_with = "de_capacity")]
+    pub capacity: u64,
+    pub lock: ScriptTemplate,
+    #[serde(rename = "type", default)]
+    pub type_: Option<ScriptTemplate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_header: Option<HeaderTemplate>,
+    /// The label of outputs in the CellRegistry after the transaction is committed to a MockChain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_label: Option<String>,
+    /// The real out point of cells live on a MockChain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_out_point: Option<OutPointTemplate>,
+}
+
+impl CellTemplate {
+    pub fn new(capacity: u64, lock: ScriptTemplate) -> Self {
+        CellTemplate {
+            tmp_type: FullCell::Full,
+            capacity,
+            lock,
+            type_: None,
+            tmp_data: None,
+            tmp_header: None,
+            tmp_label: None,
+            tmp_out_point: None,
+        }
+    }
+
+    pub fn type_(mut self, type_: ScriptTemplate) -> Self {
+        self.type_ = Some(type_);
+        self
+    }
+
+    pub fn data(mut self, data: &[u8]) -> Self {
+        self.tmp_data = Some(util::bytes_to_hex(data));
+        self
+    }
+
+    pub fn header(mut self, header: HeaderTemplate) -> Self {
+        self.tmp_header = Some(header);
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.tmp_label = Some(label.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractKind {
+    Contract,
+    DeployedContract,
+    SharedLib,
+    DeployedSharedLib,
+}
+
+impl From<ContractType> for ContractKind {
+    fn from(type_: ContractType) -> Self {
+        match type_ {
+            ContractType::Contract => ContractKind::Contract,
+            ContractType::DeployedContract => ContractKind::DeployedContract,
+            ContractType::SharedLib => ContractKind::SharedLib,
+            ContractType::DeployedSharedLib => ContractKind::DeployedSharedLib,
+        }
+    }
+}
+
+/// A contract loaded from its binary, see binary_registry for where the binary is found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContractTemplate {
+    pub tmp_type: ContractKind,
+    pub tmp_file_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_header: Option<HeaderTemplate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum DepGroup {
+    #[default]
+    #[serde(rename = "dep_group")]
+    DepGroup,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepGroupTemplate {
+    tmp_type: DepGroup,
+    pub tmp_members: Vec<ContractTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CellDepTemplate {
+    Contract(ContractTemplate),
+    DepGroup(DepGroupTemplate),
+    Cell(CellTemplate),
+    Ref(CellRef),
+}
+
+impl CellDepTemplate {
+    pub fn contract(name: &str, type_: ContractType) -> Self {
+        CellDepTemplate::Contract(ContractTemplate {
+            tmp_type: type_.into(),
+            tmp_file_name: name.to_string(),
+            tmp_header: None,
+        })
+    }
+
+    pub fn dep_group(members: Vec<(&str, ContractType)>) -> Self {
+        CellDepTemplate::DepGroup(DepGroupTemplate {
+            tmp_type: DepGroup::DepGroup,
+            tmp_members: members
+                .into_iter()
+                .map(|(name, type_)| ContractTemplate {
+                    tmp_type: type_.into(),
+                    tmp_file_name: name.to_string(),
+                    tmp_header: None,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InputCell {
+    Cell(Box<CellTemplate>),
+    Ref(CellRef),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputTemplate {
+    pub previous_output: InputCell,
+    #[serde(default, deserialize_with = "de_u64")]
+    pub since: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_signer: Option<Signer>,
+}
+
+impl InputTemplate {
+    pub fn new(cell: CellTemplate) -> Self {
+        InputTemplate {
+            previous_output: InputCell::Cell(Box::new(cell)),
+            since: 0,
+            tmp_signer: None,
+        }
+    }
+
+    pub fn by_ref(cell_ref: CellRef) -> Self {
+        InputTemplate {
+            previous_output: InputCell::Ref(cell_ref),
+            since: 0,
+            tmp_signer: None,
+        }
+    }
+
+    pub fn since(mut self, since: u64) -> Self {
+        self.since = since;
+        self
+    }
+
+    pub fn signer(mut self, signer: Signer) -> Self {
+        self.tmp_signer = Some(signer);
+        self
+    }
+}
+
+/// The activation of a hardfork, it is an epoch number or a boolean which means activated from the genesis or never.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Activation {
+    Epoch(u64),
+    Enabled(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HardforksTemplate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ckb2021: Option<Activation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ckb2023: Option<Activation>,
+}
+
+/// The `tmp_env` field, see ChainEnv::apply_json.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvTemplate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tip: Option<HeaderTemplate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardforks: Option<HardforksTemplate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_version: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TxTemplate {
+    #[serde(default)]
+    pub header_deps: Vec<HeaderTemplate>,
+    #[serde(default)]
+    pub cell_deps: Vec<CellDepTemplate>,
+    #[serde(default)]
+    pub inputs: Vec<InputTemplate>,
+    #[serde(default)]
+    pub outputs: Vec<CellTemplate>,
+    #[serde(default)]
+    pub witnesses: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmp_env: Option<EnvTemplate>,
+}
+
+impl TxTemplate {
+    pub fn new() -> Self {
+        TxTemplate::default()
+    }
+
+    /// Build the typed template from a JSON template, e.g. the one generated by TemplateGenerator.
+    pub fn from_value(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("The typed template should always be serializable.")
+    }
+
+    pub fn push_header_dep(&mut self, header: HeaderTemplate) -> usize {
+        self.header_deps.push(header);
+        self.header_deps.len() - 1
+    }
+
+    pub fn push_cell_dep(&mut self, cell_dep: CellDepTemplate) -> usize {
+        self.cell_deps.push(cell_dep);
+        self.cell_deps.len() - 1
+    }
+
+    pub fn push_input(&mut self, input: InputTemplate) -> usize {
+        self.inputs.push(input);
+        self.inputs.len() - 1
+    }
+
+    pub fn push_output(&mut self, output: CellTemplate) -> usize {
+        self.outputs.push(output);
+        self.outputs.len() - 1
+    }
+
+    pub fn push_witness(&mut self, witness: &[u8]) -> usize {
+        self.witnesses.push(util::bytes_to_hex(witness));
+        self.witnesses.len() - 1
+    }
+
+    pub fn set_env(&mut self, env: EnvTemplate) {
+        self.tmp_env = Some(env);
+    }
+}
+
+// The numbers in templates could also be strings like "0x10" or "100 CKB", so they are parsed by the same helpers as
+// TemplateParser. The errors are returned instead of panicking, and serde_json reports where the value is.
+
+fn de_capacity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    util::try_parse_json_capacity(&value).map_err(de_error)
+}
+
+fn de_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    util::try_parse_json_u64(&value).map_err(de_error)
+}
+
+fn de_u32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    util::try_parse_json_u32(&value).map_err(de_error)
+}
+
+fn de_opt_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    de_opt(deserializer, util::try_parse_json_u64)
+}
+
+fn de_opt_u32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    de_opt(deserializer, util::try_parse_json_u32)
+}
+
+fn de_opt_u128<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u128>, D::Error> {
+    de_opt(deserializer, util::try_parse_json_u128)
+}
+
+fn de_opt_timestamp_ms<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    de_opt(deserializer, util::try_parse_json_timestamp_ms)
+}
+
+fn de_opt<'de, D: Deserializer<'de>, T>(
+    deserializer: D,
+    parse: fn(&Value) -> Result<T, String>,
+) -> Result<Option<T>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    parse(&value).map(Some).map_err(de_error)
+}
+
+fn de_error<E: de::Error>(err: String) -> E {
+    E::custom(format!("the value {}", err))
+}
+
+// The u128 is out of the range of JSON numbers, so it is serialized as hex.
+fn ser_opt_u128<S: Serializer>(value: &Option<u128>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(val) => serializer.serialize_str(&format!("{:#x}", val)),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::util::constants::ONE_CKB;
+    use crate::util::signer::MultisigConfig;
+    use crate::util::template_generator::{gen_multisig_lock, TemplateGenerator};
+    use crate::util::template_parser::test_tx;
+
+    fn always_success_tx() -> TxTemplate {
+        let mut tx = TxTemplate::new();
+        tx.push_cell_dep(CellDepTemplate::contract(
+            "always_success",
+            ContractType::DeployedContract,
+        ));
+        tx.push_input(InputTemplate::new(CellTemplate::new(
+            100 * ONE_CKB,
+            ScriptTemplate::by_name("always_success"),
+        )));
+        tx.push_output(
+            CellTemplate::new(99 * ONE_CKB, ScriptTemplate::by_name("always_success").args(&[1])).data(&[1, 2]),
+        );
+        tx.push_witness(&[]);
+        tx
+    }
+
+    #[test]
+    fn test_typed_template() {
+        let tx = always_success_tx();
+        assert_eq!(
+            tx.to_value(),
+            json!({
+                "header_deps": [],
+                "cell_deps": [{ "tmp_type": "deployed_contract", "tmp_file_name": "always_success" }],
+                "inputs": [{
+                    "previous_output": {
+                        "tmp_type": "full",
+                        "capacity": 100 * ONE_CKB,
+                        "lock": { "code_hash": "{{always_success}}", "hash_type": "type", "args": "" },
+                        "type": null
+                    },
+                    "since": 0
+                }],
+                "outputs": [{
+                    "tmp_type": "full",
+                    "capacity": 99 * ONE_CKB,
+                    "lock": { "code_hash": "{{always_success}}", "hash_type": "type", "args": "0x01" },
+                    "type": null,
+                    "tmp_data": "0x0102"
+                }],
+                "witnesses": ["0x"]
+            })
+        );
+        assert_eq!(TxTemplate::from_value(&tx.to_value()).unwrap(), tx);
+
+        test_tx(tx.to_value());
+    }
+
+    #[test]
+    fn test_typed_template_from_json() {
+        // Literals like "100 CKB" are parsed the same way as TemplateParser.
+        let tx = TxTemplate::from_value(&json!({
+            "cell_deps": [{ "tmp_ref": "config" }],
+            "inputs": [{
+                "previous_output": { "out_point": { "tx_hash": "0x01", "index": "0x1" } },
+                "since": "0x10"
+            }],
+            "outputs": [{
+                "tmp_type": "full",
+                "capacity": "1.5 CKB",
+                "lock": { "code_hash": "{{always_success}}" }
+            }],
+            "tmp_env": { "hardforks": { "ckb2023": false } }
+        }))
+        .unwrap();
+        assert_eq!(
+            tx.cell_deps[0],
+            CellDepTemplate::Ref(CellRef::Label {
+                tmp_ref: String::from("config")
+            })
+        );
+        assert_eq!(tx.inputs[0].since, 16);
+        assert_eq!(tx.outputs[0].capacity, 150_000_000);
+        assert_eq!(tx.outputs[0].lock.hash_type, HashType::Type);
+
+        // Typos are caught when building from JSON, as unknown fields are denied.
+        assert!(
+            TxTemplate::from_value(&json!({ "outputs": [{ "tmp_type": "full", "capacity": 0, "lok": {} }] })).is_err()
+        );
+        let err = TxTemplate::from_value(&json!({ "header_deps": [{ "number": 1, "timestmap": 1000 }] })).unwrap_err();
+        assert!(err.to_string().contains("timestmap"), "{}", err);
+    }
+
+    #[test]
+    fn test_typed_template_from_json_invalid() {
+        // The invalid values are returned as errors instead of panicking.
+        let err = TxTemplate::from_value(&json!({
+            "outputs": [{
+                "tmp_type": "full",
+                "capacity": "142 BTC",
+                "lock": { "code_hash": "{{always_success}}" }
+            }]
+        }))
+        .unwrap_err();
+        assert!(err.to_string().contains("142 BTC"), "{}", err);
+
+        let err = TxTemplate::from_value(&json!({ "header_deps": [{ "nonce": "0xzz" }] })).unwrap_err();
+        assert!(err.to_string().contains("0xzz"), "{}", err);
+
+        // The header from RPC contains the hash, it is accepted.
+        let tx = TxTemplate::from_value(&json!({ "header_deps": [{ "number": "0x1", "hash": "0x01" }] })).unwrap();
+        assert_eq!(tx.header_deps[0].number, Some(1));
+    }
+
+    #[test]
+    fn test_typed_template_from_generator() {
+        let config = MultisigConfig::new(&["alice", "bob"], 1);
+        let mut template = TemplateGenerator::new("transfer", None);
+        template.push_contract_cell("always_success", ContractType::DeployedContract);
+        template.push_input(
+            json!({
+                "capacity": "100 CKB",
+                "lock": gen_multisig_lock(&config),
+                "header": { "number": 10, "timestamp": 1000 }
+            }),
+            None,
+            None,
+        );
+        template.sign_input_multisig(0, &config, &["alice"]);
+
+        let tx = TxTemplate::from_value(&template.as_json()).unwrap();
+        assert_eq!(
+            tx.inputs[0].tmp_signer,
+            Some(Signer::MultisigAll {
+                config,
+                signers: vec![String::from("alice")],
+            })
+        );
+        let header = match &tx.inputs[0].previous_output {
+            InputCell::Cell(cell) => cell.tmp_header.clone().unwrap(),
+            _ => panic!("inputs[0] should be a full cell"),
+        };
+        assert_eq!(header.number, Some(10));
+        assert_eq!(header.timestamp, Some(1_000_000));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use ckb_chain_spec::consensus::Consensus;
+use ckb_types::core::cell::{CellMeta, ResolvedTransaction};
+use ckb_types::core::{Capacity, ScriptHashType, TransactionView};
+use ckb_types::packed::{Byte32, CellOutput, OutPoint, OutPointVec, Script};
+use ckb_types::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintMode {
+    Off,
+    Warn,
+    Deny,
+}
+
+impl FromStr for LintMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(LintMode::Off),
+            "warn" => Ok(LintMode::Warn),
+            "deny" => Ok(LintMode::Deny),
+            _ => Err("Environment variable TX_LINT only support \"off\", \"warn\" and \"deny\"."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    /// The transaction is still valid, but it is probably not what the test means.
+    Warning,
+    /// The transaction breaks the consensus rules, so it will be rejected whatever the scripts return.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub level: LintLevel,
+    /// The field which the issue is found in, like `outputs[0]` or `cell_deps[1]`.
+    pub field: String,
+    pub message: String,
+}
+
+impl LintIssue {
+    pub fn warning(field: impl Into<String>, message: impl Into<String>) -> Self {
+        LintIssue {
+            level: LintLevel::Warning,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn error(field: impl Into<String>, message: impl Into<String>) -> Self {
+        LintIssue {
+            level: LintLevel::Error,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = match self.level {
+            LintLevel::Warning => "warning",
+            LintLevel::Error => "error",
+        };
+        write!(f, "[{}] {}: {}", level, self.field, self.message)
+    }
+}
+
+/// Check the rules which only need the transaction itself, they must be checked before the transaction is resolved.
+///
+/// - The inputs should be unique.
+/// - The witnesses should be no less than the inputs.
+pub fn check_tx(tx: &TransactionView) -> Vec<LintIssue> {
+    let mut issues = vec![];
+
+    let mut seen = HashSet::new();
+    for (i, out_point) in tx.input_pts_iter().enumerate() {
+        if !seen.insert(out_point.clone()) {
+            issues.push(LintIssue::error(
+                format!("inputs[{}]", i),
+                format!("the out point {} is spent more than once", out_point),
+            ));
+        }
+    }
+
+    if tx.witnesses().len() < tx.inputs().len() {
+        issues.push(LintIssue::warning(
+            "witnesses",
+            format!(
+                "there are {} witnesses for {} inputs, lock scripts usually require one witness per input",
+                tx.witnesses().len(),
+                tx.inputs().len()
+            ),
+        ));
+    }
+
+    issues
+}
+
+/// Check the rules which need the resolved cells.
+///
+/// - The capacity of outputs should be no less than their occupied capacity.
+/// - The capacity of inputs should be no less than outputs, except for withdrawing from NervosDAO.
+/// - Every script should be found in cell_deps by its code_hash and hash_type.
+/// - Every cell dep should be used by some script, the dep group is used if any of its members is used.
+pub fn check_resolved_tx(rtx: &ResolvedTransaction, consensus: &Consensus) -> Vec<LintIssue> {
+    let mut issues = vec![];
+    let tx = &rtx.transaction;
+
+    for (i, (output, data)) in tx.outputs_with_data_iter().enumerate() {
+        let capacity: Capacity = output.capacity().unpack();
+        match output.occupied_capacity(Capacity::bytes(data.len()).expect("The data size should not overflow.")) {
+            Ok(occupied) if capacity < occupied => issues.push(LintIssue::error(
+                format!("outputs[{}]", i),
+                format!(
+                    "the capacity {} is less than the occupied capacity {}",
+                    capacity.as_u64(),
+                    occupied.as_u64()
+                ),
+            )),
+            Ok(_) => {}
+            Err(err) => issues.push(LintIssue::error(format!("outputs[{}]", i), err.to_string())),
+        }
+    }
+
+    let dao_type_hash = consensus.dao_type_hash();
+    let is_dao_withdraw = rtx.resolved_inputs.iter().any(|cell| {
+        cell.cell_output
+            .type_()
+            .to_opt()
+            .map(|script| script.code_hash() == dao_type_hash)
+            .unwrap_or(false)
+    });
+    let inputs_capacity = sum_capacity(rtx.resolved_inputs.iter().map(|cell| cell.cell_output.clone()));
+    let outputs_capacity = sum_capacity(tx.outputs().into_iter());
+    if !is_dao_withdraw && inputs_capacity < outputs_capacity {
+        issues.push(LintIssue::error(
+            "outputs",
+            format!(
+                "the capacity of outputs {} is more than the capacity of inputs {}",
+                outputs_capacity, inputs_capacity
+            ),
+        ));
+    }
+
+    let mut scripts = vec![];
+    for (i, cell) in rtx.resolved_inputs.iter().enumerate() {
+        scripts.push((format!("inputs[{}].lock", i), cell.cell_output.lock()));
+        if let Some(script) = cell.cell_output.type_().to_opt() {
+            scripts.push((format!("inputs[{}].type", i), script));
+        }
+    }
+    for (i, output) in tx.outputs().into_iter().enumerate() {
+        if let Some(script) = output.type_().to_opt() {
+            scripts.push((format!("outputs[{}].type", i), script));
+        }
+    }
+
+    let mut used_deps = HashSet::new();
+    for (field, script) in scripts.iter() {
+        match rtx.resolved_cell_deps.iter().find(|cell| is_script_code(cell, script)) {
+            Some(cell) => {
+                used_deps.insert(cell.out_point.clone());
+            }
+            None => issues.push(LintIssue::error(
+                field.to_owned(),
+                format!(
+                    "no cell dep matches the code_hash {} with hash_type {:?}",
+                    script.code_hash(),
+                    ScriptHashType::try_from(script.hash_type()).unwrap_or(ScriptHashType::Type)
+                ),
+            )),
+        }
+    }
+
+    for (i, cell_dep) in tx.cell_deps_iter().enumerate() {
+        let out_point = cell_dep.out_point();
+        let is_used = match rtx.resolved_dep_groups.iter().find(|cell| cell.out_point == out_point) {
+            Some(group) => dep_group_members(group).iter().any(|member| used_deps.contains(member)),
+            None => used_deps.contains(&out_point),
+        };
+        if !is_used {
+            issues.push(LintIssue::warning(
+                format!("cell_deps[{}]", i),
+                "the cell dep is not used by any script, it is fine if it is loaded by a script manually",
+            ));
+        }
+    }
+
+    issues
+}
+
+fn sum_capacity(outputs: impl Iterator<Item = CellOutput>) -> u64 {
+    outputs
+        .map(|output| Unpack::<u64>::unpack(&output.capacity()))
+        .fold(0u64, |total, capacity| total.saturating_add(capacity))
+}
+
+fn is_script_code(cell: &CellMeta, script: &Script) -> bool {
+    match ScriptHashType::try_from(script.hash_type()) {
+        Ok(ScriptHashType::Type) => cell
+            .cell_output
+            .type_()
+            .to_opt()
+            .map(|type_| type_.calc_script_hash() == script.code_hash())
+            .unwrap_or(false),
+        Ok(_) => cell_data_hash(cell)
+            .map(|hash| hash == script.code_hash())
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+fn cell_data_hash(cell: &CellMeta) -> Option<Byte32> {
+    cell.mem_cell_data_hash
+        .clone()
+        .or_else(|| cell.mem_cell_data.as_ref().map(|data| CellOutput::calc_data_hash(data)))
+}
+
+fn dep_group_members(group: &CellMeta) -> Vec<OutPoint> {
+    group
+        .mem_cell_data
+        .as_ref()
+        .and_then(|data| OutPointVec::from_slice(data).ok())
+        .map(|members| members.into_iter().collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use ckb_chain_spec::consensus::TYPE_ID_CODE_HASH;
+use ckb_types::bytes;
+use ckb_types::core::ScriptHashType;
+use ckb_types::packed::{Byte32, Script};
+use ckb_types::prelude::*;
+use lazy_static::lazy_static;
+
+use super::binary_registry::binary_names;
+
+lazy_static! {
+    /// The type IDs of all known binaries, they are the binaries in the search paths of binary_registry and the
+    /// contracts in contracts/ which may not be built yet.
+    ///
+    /// Every type ID is calculated by `type_id_script`, so it is always the same as the mocked cell of the binary.
+    pub static ref TYPE_ID_TABLE: HashMap<String, Byte32> = {
+        let mut map = HashMap::new();
+        for name in binary_names() {
+            let type_id = calc_type_id(&name);
+            map.insert(name, type_id);
+        }
+        map
+    };
+}
+
+/// The type script of the mocked cell which contains the binary.
+///
+/// It is not a real type ID, the args is the name of binary padded to 32 bytes with zeros on the left, because it is
+/// convenient to use 32 bytes as the real args are also 32 bytes. Names longer than 32 bytes are truncated.
+pub fn type_id_script(binary_name: &str) -> Script {
+    let mut args = [0u8; 32];
+    let name = binary_name.as_bytes();
+    if name.len() >= args.len() {
+        args.copy_from_slice(&name[..32]);
+    } else {
+        let offset = args.len() - name.len();
+        args[offset..].copy_from_slice(name);
+    }
+
+    Script::new_builder()
+        .code_hash(TYPE_ID_CODE_HASH.pack())
+        .hash_type(ScriptHashType::Type.into())
+        .args(bytes::Bytes::from(args.to_vec()).pack())
+        .build()
+}
+
+pub fn calc_type_id(binary_name: &str) -> Byte32 {
+    type_id_script(binary_name).calc_script_hash()
+}
+
+/// Find the type ID of the binary, return None if the binary is unknown, which is usually a typo in the name.
+pub fn get_type_id(binary_name: &str) -> Option<Byte32> {
+    TYPE_ID_TABLE.get(binary_name).cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_type_id_table() {
+        // The type IDs are the same as the ones hardcoded before.
+        assert_eq!(
+            format!("{:#x}", get_type_id("always_success").unwrap()),
+            "0x34f052fc455fce7c71f4905f223653a5fbe64261c6b2537124de00f1d52820e9"
+        );
+        assert_eq!(
+            format!("{:#x}", get_type_id("playground").unwrap()),
+            "0xca4d966895b1467702bad4038396b037d8c8f045cae9cf5a7db4eadefa347887"
+        );
+        // Every binary in deployed-scripts has a type ID without editing any table.
+        assert!(get_type_id("secp256k1_blake160_sighash_all").is_some());
+        assert!(get_type_id("not-exist").is_none());
+    }
+}
@@ -1,13 +1,14 @@
 use std::error::Error;
-use std::str;
+use std::{str, thread};
 
+use chrono::{NaiveDate, NaiveDateTime};
 use ckb_types::bytes;
 use ckb_types::packed::{Byte, Byte32, Bytes, Script};
 use ckb_types::prelude::{Builder, Entity};
 use serde_json::Value;
 
 use super::constants::*;
-use super::error;
+use super::{error, type_id};
 
 pub fn hex_to_bytes(input: &str) -> Vec<u8> {
     let hex = input.trim_start_matches("0x");
@@ -82,52 +83,248 @@ pub fn merge_json(target: &mut Value, source: Value) {
     }
 }
 
+/// Get the name of the current test, it works because each test runs in a thread named after it.
+pub fn current_test_name() -> String {
+    thread::current().name().unwrap_or("unknown").to_string()
+}
+
 pub fn get_type_id_bytes(name: &str) -> Vec<u8> {
-    hex_to_bytes(
-        TYPE_ID_TABLE
-            .get(name)
-            .expect(&format!("Can not find type ID for {}", name)),
-    )
+    type_id::get_type_id(name)
+        .unwrap_or_else(|| panic!("Can not find type ID for {}, the binary is not found.", name))
+        .raw_data()
+        .to_vec()
 }
 
 /// Parse u64 in JSON
 ///
-/// Support both **number** and **string** format.
+/// Support **number**, **string** and **hex string** format.
 pub fn parse_json_u64(field_name: &str, field: &Value, default: Option<u64>) -> u64 {
+    match default {
+        Some(val) if !is_number_or_string(field) => val,
+        _ => try_parse_json_u64(field).unwrap_or_else(|err| panic_parse_failed(field_name, err)),
+    }
+}
+
+/// Like parse_json_u64, but return the error instead of panicking, the error message is not prefixed with the field name.
+pub fn try_parse_json_u64(field: &Value) -> Result<u64, String> {
     if let Some(val) = field.as_u64() {
-        val
+        Ok(val)
     } else if let Some(val) = field.as_str() {
-        val.replace("_", "")
-            .parse()
-            .expect(&format!("{} should be u64 in string", field_name))
-    } else {
-        if let Some(val) = default {
-            return val;
+        if let Some(hex) = val.strip_prefix("0x") {
+            u64::from_str_radix(hex, 16).map_err(|_| format!("should be u64 in hex string, but got {}", val))
         } else {
-            panic!("{} is missing", field_name);
+            val.replace('_', "")
+                .parse()
+                .map_err(|_| format!("should be u64 in string, but got {}", val))
         }
+    } else {
+        Err(missing_or_invalid(field, "u64"))
     }
 }
 
 /// Parse u32 in JSON
 ///
-/// Support both **number** and **string** format.
+/// Support **number**, **string** and **hex string** format.
 pub fn parse_json_u32(field_name: &str, field: &Value, default: Option<u32>) -> u32 {
+    match default {
+        Some(val) if !is_number_or_string(field) => val,
+        _ => try_parse_json_u32(field).unwrap_or_else(|err| panic_parse_failed(field_name, err)),
+    }
+}
+
+/// Like parse_json_u32, but return the error instead of panicking, the error message is not prefixed with the field name.
+pub fn try_parse_json_u32(field: &Value) -> Result<u32, String> {
     if let Some(val) = field.as_u64() {
-        val as u32
+        u32::try_from(val).map_err(|_| format!("should be u32, but got {}", val))
     } else if let Some(val) = field.as_str() {
-        val.replace("_", "")
-            .parse()
-            .expect(&format!("{} should be u32 in string", field_name))
+        if let Some(hex) = val.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).map_err(|_| format!("should be u32 in hex string, but got {}", val))
+        } else {
+            val.replace('_', "")
+                .parse()
+                .map_err(|_| format!("should be u32 in string, but got {}", val))
+        }
     } else {
-        if let Some(val) = default {
-            return val;
+        Err(missing_or_invalid(field, "u32"))
+    }
+}
+
+/// Parse timestamp in seconds in JSON
+///
+/// Support the formats of parse_json_u64, and also:
+///
+/// - date-time strings in UTC like `"2021-01-21 03:34:50"` or `"2021-01-21"`;
+/// - relative offsets from TIMESTAMP like `"+30d"` or `"-1h"`, the units are s, m, h, d and y(365 days).
+pub fn parse_json_timestamp(field_name: &str, field: &Value, default: Option<u64>) -> u64 {
+    let literal = field
+        .as_str()
+        .map(try_parse_time_literal)
+        .transpose()
+        .unwrap_or_else(|err| panic_parse_failed(field_name, err))
+        .flatten();
+    match literal {
+        Some(timestamp) => timestamp,
+        None => parse_json_u64(field_name, field, default),
+    }
+}
+
+/// Parse timestamp in milliseconds in JSON, like the timestamp of block headers.
+///
+/// Numbers are treated as milliseconds already, while date-time strings and relative offsets are the same as
+/// parse_json_timestamp and then converted to milliseconds.
+pub fn parse_json_timestamp_ms(field_name: &str, field: &Value, default: Option<u64>) -> u64 {
+    match default {
+        Some(val) if !is_number_or_string(field) => val,
+        _ => try_parse_json_timestamp_ms(field).unwrap_or_else(|err| panic_parse_failed(field_name, err)),
+    }
+}
+
+/// Like parse_json_timestamp_ms, but return the error instead of panicking, the error message is not prefixed with the field name.
+pub fn try_parse_json_timestamp_ms(field: &Value) -> Result<u64, String> {
+    match field.as_str().map(try_parse_time_literal).transpose()?.flatten() {
+        Some(timestamp) => timestamp
+            .checked_mul(1000)
+            .ok_or_else(|| format!("is overflowed: {}", field)),
+        None => try_parse_json_u64(field),
+    }
+}
+
+// Return None if the string is not a date-time or a relative offset, so it can be parsed as a number.
+fn try_parse_time_literal(val: &str) -> Result<Option<u64>, String> {
+    if let Some(caps) = RE_RELATIVE_TIME.captures(val) {
+        let amount: u64 = caps[2]
+            .replace('_', "")
+            .parse()
+            .map_err(|_| format!("should be a relative time like \"+30d\", but got {}", val))?;
+        let unit = match &caps[3] {
+            "s" => 1,
+            "m" => 60,
+            "h" => HOUR_SEC,
+            "d" => DAY_SEC,
+            _ => YEAR_SEC,
+        };
+        let timestamp = amount.checked_mul(unit).and_then(|offset| {
+            if &caps[1] == "+" {
+                TIMESTAMP.checked_add(offset)
+            } else {
+                TIMESTAMP.checked_sub(offset)
+            }
+        });
+
+        return timestamp.map(Some).ok_or_else(|| format!("is overflowed: {}", val));
+    }
+
+    let datetime = NaiveDateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(val, "%Y-%m-%d").map(|date| date.and_hms_opt(0, 0, 0).unwrap()));
+    match datetime {
+        Ok(datetime) => {
+            let timestamp = datetime.and_utc().timestamp();
+            if timestamp < 0 {
+                return Err(format!("should not be earlier than 1970-01-01, but got {}", val));
+            }
+            Ok(Some(timestamp as u64))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parse capacity in shannons in JSON
+///
+/// Support the formats of parse_json_u64, and also literals like `"142 CKB"`, `"1.5 CKB"` or `"100 shannon"`.
+pub fn parse_json_capacity(field_name: &str, field: &Value, default: Option<u64>) -> u64 {
+    match default {
+        Some(val) if !is_number_or_string(field) => val,
+        _ => try_parse_json_capacity(field).unwrap_or_else(|err| panic_parse_failed(field_name, err)),
+    }
+}
+
+/// Like parse_json_capacity, but return the error instead of panicking, the error message is not prefixed with the field name.
+pub fn try_parse_json_capacity(field: &Value) -> Result<u64, String> {
+    let val = match field.as_str() {
+        Some(val) => val.trim(),
+        None => return try_parse_json_u64(field),
+    };
+    let caps = match RE_CAPACITY.captures(val) {
+        Some(caps) => caps,
+        None => return try_parse_json_u64(field),
+    };
+
+    let integer: u64 = caps[1]
+        .replace('_', "")
+        .parse()
+        .map_err(|_| format!("should be a capacity like \"142 CKB\", but got {}", val))?;
+    let fraction = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+    let capacity = match &caps[3] {
+        "CKB" | "ckb" => {
+            // 1 CKB is 10^8 shannons, so at most 8 decimal places are allowed.
+            if fraction.len() > 8 {
+                return Err(format!("should have at most 8 decimal places, but got {}", val));
+            }
+            let fraction_shannons: u64 = if fraction.is_empty() {
+                0
+            } else {
+                format!("{:0<8}", fraction).parse().unwrap()
+            };
+            integer
+                .checked_mul(ONE_CKB)
+                .and_then(|shannons| shannons.checked_add(fraction_shannons))
+        }
+        _ => {
+            if !fraction.is_empty() {
+                return Err(format!("should be an integer in shannons, but got {}", val));
+            }
+            Some(integer)
+        }
+    };
+
+    capacity.ok_or_else(|| format!("is overflowed: {}", val))
+}
+
+/// Parse u128 in JSON
+///
+/// Support **number**, **string** and **hex string** format, because u128 like the nonce of headers can not be
+/// represented by a JSON number in most of the time.
+pub fn parse_json_u128(field_name: &str, field: &Value, default: Option<u128>) -> u128 {
+    match default {
+        Some(val) if !is_number_or_string(field) => val,
+        _ => try_parse_json_u128(field).unwrap_or_else(|err| panic_parse_failed(field_name, err)),
+    }
+}
+
+/// Like parse_json_u128, but return the error instead of panicking, the error message is not prefixed with the field name.
+pub fn try_parse_json_u128(field: &Value) -> Result<u128, String> {
+    if let Some(val) = field.as_u64() {
+        Ok(val as u128)
+    } else if let Some(val) = field.as_str() {
+        if let Some(hex) = val.strip_prefix("0x") {
+            u128::from_str_radix(hex, 16).map_err(|_| format!("should be u128 in hex string, but got {}", val))
         } else {
-            panic!("{} is missing", field_name);
+            val.replace('_', "")
+                .parse()
+                .map_err(|_| format!("should be u128 in string, but got {}", val))
         }
+    } else {
+        Err(missing_or_invalid(field, "u128"))
     }
 }
 
+// The numbers could be JSON numbers or strings, other values are treated as missing so the default value is used.
+fn is_number_or_string(field: &Value) -> bool {
+    field.is_u64() || field.is_string()
+}
+
+fn missing_or_invalid(field: &Value, expected: &str) -> String {
+    if field.is_null() {
+        String::from("is missing")
+    } else {
+        format!("should be {}, but got {}", expected, field)
+    }
+}
+
+fn panic_parse_failed(field_name: &str, err: String) -> ! {
+    panic!("{} {}", field_name, err)
+}
+
 /// Parse u8 in JSON
 pub fn parse_json_u8(field_name: &str, field: &Value, default: Option<u8>) -> u8 {
     if let Some(val) = field.as_u64() {
@@ -194,3 +391,60 @@ pub fn parse_json_array<'a>(field_name: &str, field: &'a Value) -> &'a [Value] {
         .map(|v| v.as_slice())
         .expect(&format!("{} is missing", field_name))
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_json_timestamp() {
+        assert_eq!(parse_json_timestamp("timestamp", &json!(TIMESTAMP), None), TIMESTAMP);
+        assert_eq!(
+            parse_json_timestamp("timestamp", &json!("2021-01-21 03:34:50"), None),
+            TIMESTAMP
+        );
+        assert_eq!(
+            parse_json_timestamp("timestamp", &json!("2022-10-18 12:00:00"), None),
+            TIMESTAMP_20221018
+        );
+        assert_eq!(
+            parse_json_timestamp("timestamp", &json!("2022-10-18"), None),
+            TIMESTAMP_20221018 - 12 * HOUR_SEC
+        );
+        assert_eq!(
+            parse_json_timestamp("timestamp", &json!("+30d"), None),
+            TIMESTAMP + 30 * DAY_SEC
+        );
+        assert_eq!(
+            parse_json_timestamp("timestamp", &json!("-1h"), None),
+            TIMESTAMP - HOUR_SEC
+        );
+        assert_eq!(
+            parse_json_timestamp_ms("timestamp", &json!("+1m"), None),
+            (TIMESTAMP + 60) * 1000
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "outputs[0].header.timestamp should be u64 in string")]
+    fn test_parse_json_timestamp_panic() {
+        parse_json_timestamp("outputs[0].header.timestamp", &json!("2021-13-01"), None);
+    }
+
+    #[test]
+    fn test_parse_json_capacity() {
+        assert_eq!(parse_json_capacity("capacity", &json!(100), None), 100);
+        assert_eq!(parse_json_capacity("capacity", &json!("142 CKB"), None), 142 * ONE_CKB);
+        assert_eq!(parse_json_capacity("capacity", &json!("1.5 CKB"), None), 150_000_000);
+        assert_eq!(parse_json_capacity("capacity", &json!("0.00000001CKB"), None), 1);
+        assert_eq!(parse_json_capacity("capacity", &json!("10_000 shannon"), None), 10_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "outputs[0].capacity should have at most 8 decimal places")]
+    fn test_parse_json_capacity_panic() {
+        parse_json_capacity("outputs[0].capacity", &json!("0.000000001 CKB"), None);
+    }
+}